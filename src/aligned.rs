@@ -0,0 +1,256 @@
+//! Truncation to multiples of a block or page size.
+
+use crate::{Truncate, TruncateError};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Cursor, Error},
+};
+
+/// The direction in which a length is rounded to a multiple of the alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Align {
+    /// Round down, removing the partial block at the end.
+    Down,
+    /// Round up, completing the partial block at the end.
+    Up,
+}
+
+impl Align {
+    /// Rounds `len` to a multiple of `alignment`, or returns `None` if rounding up overflows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Align;
+    /// assert_eq!(Align::Down.apply(5000, 4096), Some(4096));
+    /// assert_eq!(Align::Up.apply(5000, 4096), Some(8192));
+    /// assert_eq!(Align::Up.apply(8192, 4096), Some(8192));
+    /// ```
+    pub fn apply(self, len: u64, alignment: u64) -> Option<u64> {
+        assert!(alignment != 0, "alignment must not be zero");
+
+        match self {
+            Align::Down => Some(len - len % alignment),
+            Align::Up => match len % alignment {
+                0 => Some(len),
+                rem => len.checked_add(alignment - rem),
+            },
+        }
+    }
+}
+
+/// A trait for truncating objects to a multiple of a block size, so no partial block is left at
+/// the end.
+///
+/// All methods return the new length. Rounding up may extend the object, which fails for objects
+/// that don't support growing just like [`Truncate::truncate`].
+pub trait TruncateAligned: Truncate {
+    /// Returns the preferred block size of the object, or `None` if it doesn't have one.
+    ///
+    /// The default implementation returns `None`, which is used for in-memory buffers.
+    fn block_size(&self) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Truncate the object to `new_len` rounded to a multiple of its [block
+    /// size](TruncateAligned::block_size).
+    ///
+    /// Fails with [`TruncateError::Unsupported`] if the object doesn't have a block size, use
+    /// [`truncate_aligned_to`](TruncateAligned::truncate_aligned_to) for these.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::{Align, TruncateAligned};
+    /// let mut file = tempfile::tempfile().unwrap();
+    /// file.set_len(10).unwrap();
+    ///
+    /// let block_size = file.block_size().unwrap();
+    /// # if block_size.is_none() { return; }
+    /// let new_len = file.truncate_aligned(10, Align::Up).unwrap();
+    /// assert_eq!(Some(new_len), block_size);
+    /// ```
+    fn truncate_aligned(&mut self, new_len: u64, align: Align) -> Result<u64, Self::Error> {
+        match self.block_size()? {
+            Some(alignment) => self.truncate_aligned_to(new_len, alignment, align),
+            None => Err(TruncateError::Unsupported.into()),
+        }
+    }
+
+    /// Truncate the object to `new_len` rounded to a multiple of `alignment`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::{Align, TruncateAligned};
+    /// let mut v = vec![0; 100];
+    /// assert_eq!(v.truncate_aligned_to(70, 32, Align::Down).unwrap(), 64);
+    /// assert_eq!(v.len(), 64);
+    /// ```
+    fn truncate_aligned_to(
+        &mut self,
+        new_len: u64,
+        alignment: u64,
+        align: Align,
+    ) -> Result<u64, Self::Error> {
+        let aligned = align
+            .apply(new_len, alignment)
+            .ok_or(TruncateError::TooLarge { requested: new_len })?;
+
+        self.truncate_u64(aligned)?;
+        Ok(aligned)
+    }
+}
+
+/// Uses `st_blksize` of the file, or `f_bsize` of its filesystem if that is zero. Files don't
+/// have a block size on other platforms.
+pub(crate) fn file_block_size(file: &File) -> Result<Option<u64>, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::{fs::MetadataExt, io::AsRawFd};
+
+        let block_size = file.metadata()?.blksize();
+        if block_size != 0 {
+            return Ok(Some(block_size));
+        }
+
+        let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: The file descriptor is valid and `stat` is large enough for the result.
+        if unsafe { libc::fstatvfs(file.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: `fstatvfs` succeeded, so it has initialized `stat`.
+        // `f_bsize` is narrower than `u64` on some platforms
+        #[allow(clippy::unnecessary_cast)]
+        let block_size = unsafe { stat.assume_init() }.f_bsize as u64;
+
+        Ok(Some(block_size).filter(|&b| b != 0))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        Ok(None)
+    }
+}
+
+impl TruncateAligned for File {
+    fn block_size(&self) -> Result<Option<u64>, Error> {
+        file_block_size(self)
+    }
+}
+
+impl TruncateAligned for &File {
+    fn block_size(&self) -> Result<Option<u64>, Error> {
+        file_block_size(self)
+    }
+}
+
+impl TruncateAligned for Vec<u8> {}
+
+impl TruncateAligned for VecDeque<u8> {}
+
+impl<T> TruncateAligned for Cursor<T>
+where
+    T: TruncateAligned,
+{
+    /// Delegates to the contained object.
+    fn block_size(&self) -> Result<Option<u64>, Self::Error> {
+        self.get_ref().block_size()
+    }
+}
+
+impl<T> TruncateAligned for &mut T
+where
+    T: TruncateAligned,
+{
+    fn block_size(&self) -> Result<Option<u64>, Self::Error> {
+        (**self).block_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Align, TruncateAligned};
+    use crate::{Len, TruncateError};
+    use std::io::Cursor;
+
+    #[test]
+    fn apply() {
+        assert_eq!(Align::Down.apply(0, 512), Some(0));
+        assert_eq!(Align::Up.apply(0, 512), Some(0));
+        assert_eq!(Align::Down.apply(511, 512), Some(0));
+        assert_eq!(Align::Up.apply(1, 512), Some(512));
+        assert_eq!(Align::Down.apply(1024, 512), Some(1024));
+        assert_eq!(Align::Up.apply(1024, 512), Some(1024));
+        assert_eq!(Align::Up.apply(10, 3), Some(12));
+        assert_eq!(Align::Up.apply(u64::MAX, 2), None);
+        assert_eq!(Align::Down.apply(u64::MAX, 2), Some(u64::MAX - 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_alignment() {
+        let _ = Align::Down.apply(10, 0);
+    }
+
+    #[test]
+    fn vec() {
+        let mut v = vec![0; 100];
+        assert_eq!(v.block_size().unwrap(), None);
+
+        let e = v.truncate_aligned(50, Align::Down).unwrap_err();
+        assert!(matches!(e, TruncateError::Unsupported));
+        assert_eq!(v.len(), 100);
+
+        assert_eq!(v.truncate_aligned_to(50, 16, Align::Up).unwrap(), 64);
+        assert_eq!(v.len(), 64);
+
+        let e = v.truncate_aligned_to(50, 128, Align::Up).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+
+        let e = v.truncate_aligned_to(u64::MAX, 2, Align::Up).unwrap_err();
+        assert!(matches!(e, TruncateError::TooLarge { .. }));
+    }
+
+    #[test]
+    fn cursor() {
+        let mut c = Cursor::new(vec![0; 100]);
+        c.set_position(90);
+        assert_eq!(c.truncate_aligned_to(90, 32, Align::Down).unwrap(), 64);
+        assert_eq!(c.position(), 64);
+    }
+
+    #[test]
+    fn file() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.set_len(10_000).unwrap();
+
+        let block_size = match f.block_size().unwrap() {
+            Some(b) => b,
+            None => return,
+        };
+
+        let new_len = f.truncate_aligned(block_size + 1, Align::Down).unwrap();
+        assert_eq!(new_len, block_size);
+        assert_eq!(Len::len(&f).unwrap(), block_size);
+
+        let new_len = (&f).truncate_aligned(block_size + 1, Align::Up).unwrap();
+        assert_eq!(new_len, 2 * block_size);
+        assert_eq!(Len::len(&f).unwrap(), 2 * block_size);
+
+        let mut c = Cursor::new(&mut f);
+        assert_eq!(c.block_size().unwrap(), Some(block_size));
+        assert_eq!(c.truncate_aligned_to(3000, 1000, Align::Up).unwrap(), 3000);
+        assert_eq!(Len::len(&f).unwrap(), 3000);
+    }
+}
@@ -0,0 +1,404 @@
+//! Asynchronous counterpart to the [`Truncate`] trait.
+
+use crate::Truncate;
+use std::{
+    cmp,
+    future::Future,
+    io::{Cursor, Error},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+/// A trait for asynchronous IO objects that can be shortened.
+///
+/// This is the poll-based equivalent of [`Truncate`], in the style of `AsyncWrite`. Most users
+/// will want to use the [`AsyncTruncateExt::truncate`] method instead of calling
+/// [`poll_truncate`](AsyncTruncate::poll_truncate) directly.
+pub trait AsyncTruncate {
+    /// Attempt to truncate the object to the given new length in bytes.
+    ///
+    /// The behavior when `new_len` is larger than the current length of the object is
+    /// unspecified, see [`Truncate::truncate`].
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>>;
+}
+
+/// An extension trait which adds utility methods to [`AsyncTruncate`] types.
+pub trait AsyncTruncateExt: AsyncTruncate {
+    /// Truncate the object to the given new length in bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::AsyncTruncateExt;
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut v: &[u8] = &[0, 1, 2, 3];
+    /// v.truncate(3).await.unwrap();
+    /// assert_eq!(v, &[0, 1, 2]);
+    /// # });
+    /// ```
+    fn truncate(&mut self, new_len: usize) -> TruncateFuture<'_, Self>
+    where
+        Self: Unpin,
+    {
+        TruncateFuture {
+            inner: self,
+            new_len,
+        }
+    }
+}
+
+impl<T> AsyncTruncateExt for T where T: AsyncTruncate + ?Sized {}
+
+/// Future returned by [`AsyncTruncateExt::truncate`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TruncateFuture<'a, T: ?Sized> {
+    inner: &'a mut T,
+    new_len: usize,
+}
+
+impl<T> Future for TruncateFuture<'_, T>
+where
+    T: AsyncTruncate + Unpin + ?Sized,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let new_len = self.new_len;
+        Pin::new(&mut *self.inner).poll_truncate(cx, new_len)
+    }
+}
+
+/// Truncations of the runtimes' file types that are in progress.
+///
+/// The file types can't hold any state for [`AsyncTruncate`], so the state of a truncation that
+/// returned [`Poll::Pending`] is kept here until a later call to `poll_truncate` on the same
+/// file completes it.
+#[cfg(all(any(unix, windows), any(feature = "tokio", feature = "async-std")))]
+mod pending {
+    use std::{
+        future::Future,
+        io::Error,
+        pin::Pin,
+        sync::{Mutex, PoisonError},
+    };
+
+    /// A blocking truncation, which doesn't borrow the file.
+    pub(super) type Task = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+
+    pub(super) enum Stage {
+        /// Waiting for the runtime to discard its read buffer.
+        #[cfg(feature = "tokio")]
+        Seeking,
+        /// Waiting for the blocking task.
+        Truncating(Task),
+    }
+
+    /// Identifies a file by its address and raw handle. Entries left behind by an abandoned
+    /// truncation are replaced once another file with the same handle is truncated.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub(super) struct Key {
+        address: usize,
+        handle: usize,
+    }
+
+    impl Key {
+        #[cfg(unix)]
+        pub(super) fn of<F>(file: &F) -> Self
+        where
+            F: std::os::unix::io::AsRawFd,
+        {
+            Key {
+                address: file as *const F as usize,
+                handle: file.as_raw_fd() as usize,
+            }
+        }
+
+        #[cfg(windows)]
+        pub(super) fn of<F>(file: &F) -> Self
+        where
+            F: std::os::windows::io::AsRawHandle,
+        {
+            Key {
+                address: file as *const F as usize,
+                handle: file.as_raw_handle() as usize,
+            }
+        }
+    }
+
+    struct Entry {
+        key: Key,
+        new_len: usize,
+        stage: Stage,
+    }
+
+    static PENDING: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+    /// Removes and returns the stage of the truncation of the file to `new_len`, if there is one.
+    /// Other truncations of the same handle are discarded.
+    pub(super) fn take(key: Key, new_len: usize) -> Option<Stage> {
+        let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+        let i = pending.iter().position(|e| e.key.handle == key.handle)?;
+        let entry = pending.swap_remove(i);
+        (entry.key == key && entry.new_len == new_len).then_some(entry.stage)
+    }
+
+    /// Stores the stage of a truncation that is still in progress.
+    pub(super) fn put(key: Key, new_len: usize, stage: Stage) {
+        let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.push(Entry {
+            key,
+            new_len,
+            stage,
+        });
+    }
+
+    /// Polls the blocking task, storing it again if it isn't finished.
+    pub(super) fn poll_task(
+        key: Key,
+        new_len: usize,
+        mut task: Task,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Error>> {
+        let res = task.as_mut().poll(cx);
+        if res.is_pending() {
+            put(key, new_len, Stage::Truncating(task));
+        }
+        res
+    }
+}
+
+#[cfg(all(feature = "tokio", any(unix, windows)))]
+impl AsyncTruncate for tokio::fs::File {
+    /// Works like [`tokio::fs::File::set_len`]: Pending writes are flushed and buffered data that
+    /// wasn't read yet is discarded, then [`std::fs::File::set_len`] is called on a duplicate of
+    /// the underlying handle with [`tokio::task::spawn_blocking`].
+    fn poll_truncate(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        use pending::Stage;
+        use tokio::io::{AsyncSeek, AsyncWrite};
+
+        let key = pending::Key::of(&*self);
+        let mut stage = pending::take(key, new_len);
+        loop {
+            stage = match stage {
+                None => {
+                    // Also waits for any other operation in progress
+                    ready!(self.as_mut().poll_flush(cx))?;
+                    self.as_mut().start_seek(std::io::SeekFrom::Current(0))?;
+                    Some(Stage::Seeking)
+                }
+                Some(Stage::Seeking) => {
+                    if let Poll::Ready(res) = self.as_mut().poll_complete(cx) {
+                        res?;
+                    } else {
+                        pending::put(key, new_len, Stage::Seeking);
+                        return Poll::Pending;
+                    }
+
+                    let file = dup_file(&*self)?;
+                    let task = tokio::task::spawn_blocking(move || file.set_len(new_len as u64));
+                    Some(Stage::Truncating(Box::pin(async move {
+                        task.await.map_err(Error::other)?
+                    })))
+                }
+                Some(Stage::Truncating(task)) => {
+                    return pending::poll_task(key, new_len, task, cx);
+                }
+            };
+        }
+    }
+}
+
+#[cfg(all(feature = "async-std", any(unix, windows)))]
+impl AsyncTruncate for async_std::fs::File {
+    /// Polls [`async_std::fs::File::set_len`], which flushes pending writes, discards buffered
+    /// data that wasn't read yet and truncates the file on a blocking thread.
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        let key = pending::Key::of(&*self);
+        let task = match pending::take(key, new_len) {
+            Some(pending::Stage::Truncating(task)) => task,
+            // Clones share the state of the file, so the future doesn't borrow it
+            _ => {
+                let file = self.clone();
+                Box::pin(async move { file.set_len(new_len as u64).await })
+            }
+        };
+        pending::poll_task(key, new_len, task, cx)
+    }
+}
+
+/// Duplicates the handle backing an async file type, so that it can be moved to a blocking
+/// thread.
+#[cfg(all(unix, feature = "tokio"))]
+fn dup_file<F>(file: &F) -> Result<std::fs::File, Error>
+where
+    F: std::os::unix::io::AsFd,
+{
+    Ok(file.as_fd().try_clone_to_owned()?.into())
+}
+
+#[cfg(all(windows, feature = "tokio"))]
+fn dup_file<F>(file: &F) -> Result<std::fs::File, Error>
+where
+    F: std::os::windows::io::AsHandle,
+{
+    Ok(file.as_handle().try_clone_to_owned()?.into())
+}
+
+impl AsyncTruncate for Vec<u8> {
+    /// Same as the [`Truncate`] impl for `Vec<u8>`.
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        Poll::Ready(Truncate::truncate(self.get_mut(), new_len).map_err(Error::from))
+    }
+}
+
+impl AsyncTruncate for &[u8] {
+    /// Same as the [`Truncate`] impl for `&[u8]`.
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        Poll::Ready(Truncate::truncate(self.get_mut(), new_len).map_err(Error::from))
+    }
+}
+
+impl<T> AsyncTruncate for Cursor<T>
+where
+    T: AsyncTruncate + Unpin,
+{
+    /// Delegates to the contained [`AsyncTruncate`] impl. The cursor will be moved to the end of
+    /// the data if it lies in the truncated area.
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+        ready!(Pin::new(this.get_mut()).poll_truncate(cx, new_len))?;
+        this.set_position(cmp::min(new_len as u64, this.position()));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncTruncate for &mut T
+where
+    T: AsyncTruncate + Unpin + ?Sized,
+{
+    fn poll_truncate(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        new_len: usize,
+    ) -> Poll<Result<(), Error>> {
+        Pin::new(&mut **self.get_mut()).poll_truncate(cx, new_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncTruncateExt;
+    use std::{
+        future::Future,
+        io::{Cursor, ErrorKind},
+    };
+
+    fn block_on<F: Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    #[test]
+    fn vec() {
+        block_on(async {
+            let mut v: Vec<u8> = vec![0, 1, 2, 3];
+
+            AsyncTruncateExt::truncate(&mut v, 3).await.unwrap();
+            assert_eq!(v, &[0, 1, 2]);
+
+            // Error
+            let e = AsyncTruncateExt::truncate(&mut v, 4).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        });
+    }
+
+    #[test]
+    fn cursor() {
+        block_on(async {
+            let mut v: Cursor<&[u8]> = Cursor::new(&[0, 1, 2, 3]);
+
+            v.set_position(4); // end of data
+            v.truncate(3).await.unwrap();
+            assert_eq!(v.get_ref(), &[0, 1, 2]);
+            assert_eq!(v.position(), 3);
+
+            // Through a mutable reference
+            AsyncTruncateExt::truncate(&mut &mut v, 2).await.unwrap();
+            assert_eq!(v.get_ref(), &[0, 1]);
+            assert_eq!(v.position(), 2);
+        });
+    }
+
+    #[cfg(all(feature = "tokio", any(unix, windows)))]
+    #[test]
+    fn tokio_file() {
+        use std::io::SeekFrom;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+        block_on(async {
+            let mut f = tokio::fs::File::from_std(tempfile::tempfile().unwrap());
+
+            // Pending writes must land before the truncation
+            f.write_all(&[0, 1, 2, 3, 4, 5, 6, 7]).await.unwrap();
+            f.truncate(6).await.unwrap();
+            assert_eq!(f.seek(SeekFrom::End(0)).await.unwrap(), 6);
+
+            // Data that was read ahead past the new length is discarded
+            f.seek(SeekFrom::Start(0)).await.unwrap();
+            assert_eq!(f.read_u8().await.unwrap(), 0);
+            f.truncate(4).await.unwrap();
+            let mut rest = Vec::new();
+            f.read_to_end(&mut rest).await.unwrap();
+            assert_eq!(rest, &[1, 2, 3]);
+
+            // Through a wrapper, which only forwards `poll_truncate`
+            let mut c = Cursor::new(f);
+            c.set_position(4);
+            c.truncate(2).await.unwrap();
+            assert_eq!(c.position(), 2);
+            let mut f = c.into_inner();
+            assert_eq!(f.seek(SeekFrom::End(0)).await.unwrap(), 2);
+        });
+    }
+
+    #[cfg(all(feature = "async-std", any(unix, windows)))]
+    #[test]
+    fn async_std_file() {
+        use async_std::io::{prelude::SeekExt, WriteExt};
+
+        async_std::task::block_on(async {
+            let mut f = async_std::fs::File::from(tempfile::tempfile().unwrap());
+
+            f.write_all(&[0, 1, 2, 3]).await.unwrap();
+            f.truncate(3).await.unwrap();
+            assert_eq!(f.seek(std::io::SeekFrom::End(0)).await.unwrap(), 3);
+        });
+    }
+}
@@ -0,0 +1,274 @@
+//! A `truncate` command compatible with the one of GNU coreutils.
+
+use io_truncate::{SizeSpec, Truncate};
+use std::{
+    env,
+    ffi::OsString,
+    fs::{Metadata, OpenOptions},
+    io::ErrorKind,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    process,
+};
+
+const USAGE: &str = "\
+Usage: truncate OPTION... FILE...
+Shrink or extend the size of each FILE to the specified size.
+
+A FILE argument that does not exist is created.
+
+If a FILE is larger than the specified size, the extra data is lost.
+If a FILE is shorter, it is extended and the extended part reads as zero bytes.
+
+Options:
+  -c, --no-create        do not create any files
+  -o, --io-blocks        treat SIZE as number of IO blocks instead of bytes
+  -r, --reference=RFILE  base size on RFILE
+  -s, --size=SIZE        set or adjust the file size by SIZE bytes
+      --help             display this help and exit
+      --version          output version information and exit
+
+SIZE is an integer with an optional unit: K, M, G, T, P, E (powers of 1024)
+or KB, MB, ... (powers of 1000). KiB, MiB, ... are the same as K, M, ...
+
+SIZE may also be prefixed by one of the following modifying characters:
+'+' extend by, '-' reduce by, '<' at most, '>' at least,
+'/' round down to multiple of, '%' round up to multiple of.
+";
+
+#[derive(Debug, Default)]
+struct Args {
+    no_create: bool,
+    io_blocks: bool,
+    reference: Option<PathBuf>,
+    size: Option<SizeSpec>,
+    files: Vec<PathBuf>,
+}
+
+fn main() {
+    let args = match parse_args(env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("truncate: {}", msg);
+            eprintln!("Try 'truncate --help' for more information.");
+            process::exit(1);
+        }
+    };
+
+    if let Err(msg) = check_args(&args) {
+        eprintln!("truncate: {}", msg);
+        eprintln!("Try 'truncate --help' for more information.");
+        process::exit(1);
+    }
+
+    let reference = match &args.reference {
+        Some(path) => match path.metadata() {
+            Ok(m) => Some(m.len()),
+            Err(e) => {
+                eprintln!("truncate: cannot stat '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut failed = false;
+    for path in &args.files {
+        if let Err(msg) = truncate_file(&args, reference, path) {
+            eprintln!("truncate: {}", msg);
+            failed = true;
+        }
+    }
+
+    process::exit(if failed { 1 } else { 0 });
+}
+
+fn parse_args<I>(args: I) -> Result<Args, String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    let mut only_files = false;
+
+    while let Some(arg) = args.next() {
+        let s = match arg.to_str() {
+            Some(s) if !only_files && s.starts_with('-') && s != "-" => s,
+            _ => {
+                parsed.files.push(arg.into());
+                continue;
+            }
+        };
+
+        if s == "--" {
+            only_files = true;
+        } else if let Some(long) = s.strip_prefix("--") {
+            // `--size=X` or `--size X`
+            let (name, inline) = match long.find('=') {
+                Some(i) => (&long[..i], Some(OsString::from(&long[i + 1..]))),
+                None => (long, None),
+            };
+
+            match name {
+                "size" | "reference" => {
+                    let value = option_value(inline, &mut args, s)?;
+                    set_value(&mut parsed, name, value)?;
+                }
+                "help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                "version" => {
+                    println!("truncate (io-truncate) {}", env!("CARGO_PKG_VERSION"));
+                    process::exit(0);
+                }
+                _ if inline.is_none() => set_flag(&mut parsed, name)?,
+                _ => return Err(format!("unrecognized option '{}'", s)),
+            }
+        } else {
+            // Clusters of short flags like `-co`, the last one may take a value as in `-s10` or
+            // `-s 10`
+            for (i, flag) in s.char_indices().skip(1) {
+                match flag {
+                    's' | 'r' => {
+                        let rest = &s[i + 1..];
+                        let inline = Some(OsString::from(rest)).filter(|_| !rest.is_empty());
+                        let value = option_value(inline, &mut args, s)?;
+                        let name = if flag == 's' { "size" } else { "reference" };
+                        set_value(&mut parsed, name, value)?;
+                        break;
+                    }
+                    'c' => set_flag(&mut parsed, "no-create")?,
+                    'o' => set_flag(&mut parsed, "io-blocks")?,
+                    _ => return Err(format!("invalid option -- '{}'", flag)),
+                }
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn option_value<I>(inline: Option<OsString>, args: &mut I, option: &str) -> Result<OsString, String>
+where
+    I: Iterator<Item = OsString>,
+{
+    inline
+        .or_else(|| args.next())
+        .ok_or_else(|| format!("option '{}' requires an argument", option))
+}
+
+fn set_value(args: &mut Args, name: &str, value: OsString) -> Result<(), String> {
+    if name == "size" {
+        let value = value
+            .to_str()
+            .ok_or_else(|| format!("invalid number: {:?}", value))?;
+        let size = value
+            .parse()
+            .map_err(|e| format!("invalid number: '{}': {}", value, e))?;
+        args.size = Some(size);
+    } else {
+        args.reference = Some(value.into());
+    }
+    Ok(())
+}
+
+fn set_flag(args: &mut Args, name: &str) -> Result<(), String> {
+    match name {
+        "no-create" => args.no_create = true,
+        "io-blocks" => args.io_blocks = true,
+        _ => return Err(format!("unrecognized option '--{}'", name)),
+    }
+    Ok(())
+}
+
+fn check_args(args: &Args) -> Result<(), String> {
+    match (&args.size, &args.reference) {
+        (None, None) => return Err("you must specify either '--size' or '--reference'".into()),
+        (Some(size), Some(_)) if !size.is_relative() => {
+            return Err("you must specify a relative '--size' with '--reference'".into())
+        }
+        (None, Some(_)) if args.io_blocks => {
+            return Err("'--io-blocks' was specified but '--size' was not".into())
+        }
+        _ => {}
+    }
+
+    if args.files.is_empty() {
+        return Err("missing file operand".into());
+    }
+
+    Ok(())
+}
+
+/// Multiplies the value of a size by `factor`, for `--io-blocks`.
+fn scale(size: SizeSpec, factor: u64) -> Option<SizeSpec> {
+    let scale_nz = |n: NonZeroU64| NonZeroU64::new(n.get().checked_mul(factor)?);
+    Some(match size {
+        SizeSpec::Absolute(n) => SizeSpec::Absolute(n.checked_mul(factor)?),
+        SizeSpec::Extend(n) => SizeSpec::Extend(n.checked_mul(factor)?),
+        SizeSpec::Reduce(n) => SizeSpec::Reduce(n.checked_mul(factor)?),
+        SizeSpec::AtMost(n) => SizeSpec::AtMost(n.checked_mul(factor)?),
+        SizeSpec::AtLeast(n) => SizeSpec::AtLeast(n.checked_mul(factor)?),
+        SizeSpec::RoundDown(n) => SizeSpec::RoundDown(scale_nz(n)?),
+        SizeSpec::RoundUp(n) => SizeSpec::RoundUp(scale_nz(n)?),
+    })
+}
+
+fn truncate_file(args: &Args, reference: Option<u64>, path: &Path) -> Result<(), String> {
+    let mut file = match OpenOptions::new()
+        .write(true)
+        .create(!args.no_create)
+        .truncate(false)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if args.no_create && e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "cannot open '{}' for writing: {}",
+                path.display(),
+                e
+            ))
+        }
+    };
+
+    let metadata = file
+        .metadata()
+        .map_err(|e| format!("cannot fstat '{}': {}", path.display(), e))?;
+
+    let new_len = match args.size {
+        Some(mut size) => {
+            if args.io_blocks {
+                size = scale(size, block_size(&metadata)).ok_or("size too large")?;
+            }
+            size.checked_apply(reference.unwrap_or(metadata.len()))
+                .ok_or("size too large")?
+        }
+        None => reference.unwrap_or(0),
+    };
+
+    file.truncate_u64(new_len).map_err(|e| {
+        format!(
+            "failed to truncate '{}' at {} bytes: {}",
+            path.display(),
+            new_len,
+            e
+        )
+    })
+}
+
+#[cfg(unix)]
+fn block_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    match metadata.blksize() {
+        0 => 512,
+        n => n,
+    }
+}
+
+#[cfg(not(unix))]
+fn block_size(_metadata: &Metadata) -> u64 {
+    512
+}
@@ -0,0 +1,134 @@
+//! Implementations for buffered readers and writers.
+
+use crate::Truncate;
+use std::{
+    cmp,
+    io::{BufReader, BufWriter, Error, Seek, SeekFrom, Write},
+};
+
+impl<W> Truncate for BufWriter<W>
+where
+    W: Write + Truncate,
+    Error: From<W::Error>,
+{
+    type Error = Error;
+
+    /// Flushes the buffer, then delegates to the contained [`Truncate`] impl.
+    ///
+    /// Buffered data is treated as if it had already been written, so it is subject to the
+    /// truncation like all other data. Without flushing first, it would be written after the
+    /// truncation and could grow the object again. If flushing fails, the object is not truncated.
+    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
+        self.flush()?;
+        self.get_mut().truncate(new_len).map_err(Error::from)
+    }
+
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), Error> {
+        self.flush()?;
+        self.get_mut().truncate_u64(new_len).map_err(Error::from)
+    }
+}
+
+impl<R> Truncate for BufReader<R>
+where
+    R: Truncate + Seek,
+    Error: From<R::Error>,
+{
+    type Error = Error;
+
+    /// Delegates to the contained [`Truncate`] impl and discards the buffered data. The reader
+    /// will be moved to the end of the data if its position lies in the truncated area.
+    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
+        self.truncate_u64(new_len as u64)
+    }
+
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), Error> {
+        // The position of the reader, not of the contained object, which is ahead by the buffer
+        let position = self.stream_position()?;
+
+        self.get_mut().truncate_u64(new_len)?;
+        self.seek(SeekFrom::Start(cmp::min(position, new_len)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Len, Truncate};
+    use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn buf_writer() {
+        let mut w = BufWriter::new(tempfile::tempfile().unwrap());
+
+        w.write_all(b"0123").unwrap();
+        w.truncate(2).unwrap();
+        assert_eq!(Len::len(w.get_ref()).unwrap(), 2);
+
+        // Position of the file is unchanged, as with the unbuffered impl
+        w.seek(SeekFrom::Start(2)).unwrap();
+        w.write_all(b"45").unwrap();
+        w.truncate_u64(3).unwrap();
+        w.write_all(b"6").unwrap();
+        w.flush().unwrap();
+
+        let mut buf = Vec::new();
+        let mut f = w.into_inner().unwrap();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"014\x006");
+    }
+
+    #[test]
+    fn buf_writer_cursor() {
+        let mut w = BufWriter::new(Cursor::new(Vec::new()));
+
+        w.write_all(b"abcd").unwrap();
+        w.truncate(1).unwrap();
+        w.write_all(b"e").unwrap();
+        w.flush().unwrap();
+
+        assert_eq!(w.get_ref().get_ref(), b"ae");
+    }
+
+    #[test]
+    fn buf_reader() {
+        let mut r = BufReader::with_capacity(4, Cursor::new(b"012345".to_vec()));
+
+        // Fills the buffer with "0123"
+        let mut buf = [0; 1];
+        r.read_exact(&mut buf).unwrap();
+        assert_eq!(r.buffer(), b"123");
+
+        r.truncate(3).unwrap();
+        assert!(r.buffer().is_empty());
+        assert_eq!(r.stream_position().unwrap(), 1);
+
+        let mut rest = Vec::new();
+        r.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"12");
+
+        // Position past the new end is moved back
+        r.truncate(2).unwrap();
+        assert_eq!(r.stream_position().unwrap(), 2);
+        assert!(r.fill_buf().unwrap().is_empty());
+    }
+
+    #[test]
+    fn buf_reader_file() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(b"line 1\nline 2\n").unwrap();
+        f.seek(SeekFrom::Start(0)).unwrap();
+
+        let mut r = BufReader::new(f);
+        let mut line = String::new();
+        r.read_line(&mut line).unwrap();
+        assert_eq!(line, "line 1\n");
+
+        r.truncate(9).unwrap();
+        line.clear();
+        r.read_line(&mut line).unwrap();
+        assert_eq!(line, "li");
+        assert_eq!(Len::len(r.get_ref()).unwrap(), 9);
+    }
+}
@@ -0,0 +1,253 @@
+//! Implementations for buffer types of optional third-party crates.
+//!
+//! All of these shorten the buffer like the impl for `Vec<u8>`, and return an error if the new
+//! length is larger than the current length.
+
+#[cfg(feature = "bytes")]
+mod bytes_impls {
+    use crate::{check_len, Len, Truncate, TruncateError};
+    use bytes::{Bytes, BytesMut};
+
+    impl Truncate for BytesMut {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, BytesMut::len(self))?;
+            BytesMut::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl Truncate for Bytes {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, Bytes::len(self))?;
+            Bytes::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl Len for BytesMut {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(BytesMut::len(self) as u64)
+        }
+    }
+
+    impl Len for Bytes {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(Bytes::len(self) as u64)
+        }
+    }
+}
+
+#[cfg(feature = "smallvec")]
+mod smallvec_impls {
+    use crate::{check_len, Len, Truncate, TruncateError};
+    use smallvec::{Array, SmallVec};
+
+    impl<A> Truncate for SmallVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, SmallVec::len(self))?;
+            SmallVec::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl<A> Len for SmallVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(SmallVec::len(self) as u64)
+        }
+    }
+}
+
+#[cfg(feature = "arrayvec")]
+mod arrayvec_impls {
+    use crate::{check_len, Len, Truncate, TruncateError};
+    use arrayvec::ArrayVec;
+
+    impl<const CAP: usize> Truncate for ArrayVec<u8, CAP> {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, ArrayVec::len(self))?;
+            ArrayVec::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl<const CAP: usize> Len for ArrayVec<u8, CAP> {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(ArrayVec::len(self) as u64)
+        }
+    }
+}
+
+#[cfg(feature = "tinyvec")]
+mod tinyvec_impls {
+    use crate::{check_len, Len, Truncate, TruncateError};
+    use tinyvec::{Array, ArrayVec, TinyVec};
+
+    impl<A> Truncate for ArrayVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, ArrayVec::len(self))?;
+            ArrayVec::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl<A> Truncate for TinyVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, TinyVec::len(self))?;
+            TinyVec::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl<A> Len for ArrayVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(ArrayVec::len(self) as u64)
+        }
+    }
+
+    impl<A> Len for TinyVec<A>
+    where
+        A: Array<Item = u8>,
+    {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(TinyVec::len(self) as u64)
+        }
+    }
+}
+
+#[cfg(feature = "heapless")]
+mod heapless_impls {
+    use crate::{check_len, Len, Truncate, TruncateError};
+    use heapless::Vec;
+
+    impl<const N: usize> Truncate for Vec<u8, N> {
+        type Error = TruncateError;
+
+        fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+            check_len(new_len, <[u8]>::len(self))?;
+            Vec::truncate(self, new_len);
+            Ok(())
+        }
+    }
+
+    impl<const N: usize> Len for Vec<u8, N> {
+        type Error = TruncateError;
+
+        fn len(&self) -> Result<u64, TruncateError> {
+            Ok(<[u8]>::len(self) as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Len, Truncate, TruncateError};
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes() {
+        let mut b = bytes::BytesMut::from(&[0, 1, 2, 3][..]);
+        Truncate::truncate(&mut b, 3).unwrap();
+        assert_eq!(&b[..], &[0, 1, 2]);
+        assert_eq!(Len::len(&b).unwrap(), 3);
+        let e = Truncate::truncate(&mut b, 4).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+
+        let mut b = b.freeze();
+        Truncate::truncate(&mut b, 1).unwrap();
+        assert_eq!(&b[..], &[0]);
+        let e = Truncate::truncate(&mut b, 2).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn smallvec() {
+        let mut v: smallvec::SmallVec<[u8; 2]> = smallvec::smallvec![0, 1, 2, 3];
+        Truncate::truncate(&mut v, 3).unwrap();
+        assert_eq!(&v[..], &[0, 1, 2]);
+        assert_eq!(Len::len(&v).unwrap(), 3);
+        let e = Truncate::truncate(&mut v, 4).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+    }
+
+    #[cfg(feature = "arrayvec")]
+    #[test]
+    fn arrayvec() {
+        let mut v = arrayvec::ArrayVec::<u8, 8>::new();
+        v.try_extend_from_slice(&[0, 1, 2, 3]).unwrap();
+        Truncate::truncate(&mut v, 3).unwrap();
+        assert_eq!(&v[..], &[0, 1, 2]);
+        assert_eq!(Len::len(&v).unwrap(), 3);
+
+        // Larger than the length, but not the capacity
+        let e = Truncate::truncate(&mut v, 4).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+    }
+
+    #[cfg(feature = "tinyvec")]
+    #[test]
+    fn tinyvec() {
+        let mut v: tinyvec::ArrayVec<[u8; 8]> = tinyvec::array_vec!(0, 1, 2, 3);
+        Truncate::truncate(&mut v, 3).unwrap();
+        assert_eq!(&v[..], &[0, 1, 2]);
+        let e = Truncate::truncate(&mut v, 4).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+
+        let mut v: tinyvec::TinyVec<[u8; 2]> = tinyvec::tiny_vec!(0, 1, 2, 3);
+        Truncate::truncate(&mut v, 1).unwrap();
+        assert_eq!(&v[..], &[0]);
+        assert_eq!(Len::len(&v).unwrap(), 1);
+        let e = Truncate::truncate(&mut v, 2).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless() {
+        let mut v = heapless::Vec::<u8, 8>::from_slice(&[0, 1, 2, 3]).unwrap();
+        Truncate::truncate(&mut v, 3).unwrap();
+        assert_eq!(&v[..], &[0, 1, 2]);
+        assert_eq!(Len::len(&v).unwrap(), 3);
+        let e = Truncate::truncate(&mut v, 4).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+    }
+}
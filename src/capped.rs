@@ -0,0 +1,391 @@
+//! Capped log file that only keeps the newest data.
+
+use crate::{aligned::file_block_size, copy::try_collapse, Truncate};
+use std::{
+    cmp,
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Take, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 8] = *b"IOTCAP\0\x01";
+
+/// Size of the header at the start of the file. This is a full block, so that the start of the
+/// data stays aligned for collapsing ranges.
+const HEADER_LEN: u64 = 4096;
+
+/// Value of the `end` header field if the data extends to the end of the file.
+const END_OF_FILE: u64 = u64::MAX;
+
+/// The header stored at the start of a [`CappedFile`].
+///
+/// All offsets are relative to the end of the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    /// Offset of the oldest valid byte.
+    start: u64,
+    /// Offset after the newest valid byte, or [`END_OF_FILE`]. Only set while compacting.
+    end: u64,
+}
+
+impl Header {
+    fn read(mut file: &File) -> Result<Self, Error> {
+        let mut buf = [0; 24];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut buf)?;
+
+        if buf[..8] != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not a capped file (invalid header)",
+            ));
+        }
+
+        let mut field = [0; 8];
+        field.copy_from_slice(&buf[8..16]);
+        let start = u64::from_le_bytes(field);
+        field.copy_from_slice(&buf[16..24]);
+        let end = u64::from_le_bytes(field);
+
+        Ok(Header { start, end })
+    }
+
+    fn write(&self, mut file: &File) -> Result<(), Error> {
+        let mut buf = [0; 24];
+        buf[..8].copy_from_slice(&MAGIC);
+        buf[8..16].copy_from_slice(&self.start.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.end.to_le_bytes());
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&buf)
+    }
+}
+
+/// A log file that keeps only the newest `cap` bytes written to it.
+///
+/// Writes are appended to the end of the file. Once more than `cap` bytes are stored, the oldest
+/// data is discarded. The file starts with a header recording the offset of the oldest valid
+/// byte, which is updated before any data is discarded. The file is synced between the steps of
+/// reclaiming discarded space, so even a power loss never exposes partially discarded data to
+/// readers.
+///
+/// Discarded data is reclaimed by removing it from the start of the file. On Linux this uses
+/// `FALLOC_FL_COLLAPSE_RANGE` if the filesystem supports it, keeping the file at most one block
+/// larger than the header and the cap. Otherwise the valid data is copied to the start of the
+/// file once it fits into the discarded area, followed by a truncation, so that the file grows to
+/// at most the header plus twice the cap.
+///
+/// The file must not be modified by other means while it is open.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::CappedFile;
+/// # use std::io::Write;
+/// let dir = tempfile::tempdir().unwrap();
+/// let mut f = CappedFile::open(dir.path().join("log"), 8).unwrap();
+///
+/// f.write_all(b"hello ").unwrap();
+/// f.write_all(b"world").unwrap();
+/// assert_eq!(f.read_all().unwrap(), b"lo world");
+/// ```
+#[derive(Debug)]
+pub struct CappedFile {
+    file: File,
+    cap: u64,
+    header: Header,
+    /// Length of the valid data.
+    len: u64,
+    /// Whether collapsing ranges should be attempted.
+    collapse: bool,
+}
+
+impl CappedFile {
+    /// Opens or creates the capped file at `path`, keeping at most `cap` bytes of data.
+    ///
+    /// An existing file is recovered if a previous compaction was interrupted. If it holds more
+    /// than `cap` bytes, the oldest data is discarded. Returns an error of kind
+    /// [`InvalidData`](ErrorKind::InvalidData) if the file is not empty and doesn't start with a
+    /// valid header.
+    pub fn open<P>(path: P, cap: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut header = Header {
+            start: 0,
+            end: END_OF_FILE,
+        };
+
+        let physical = file.metadata()?.len();
+        if physical == 0 {
+            header.write(&file)?;
+            file.set_len(HEADER_LEN)?;
+        } else {
+            header = Header::read(&file)?;
+        }
+
+        let mut this = CappedFile {
+            file,
+            cap,
+            header,
+            len: 0,
+            collapse: cfg!(target_os = "linux"),
+        };
+        this.recover()?;
+        this.discard()?;
+        Ok(this)
+    }
+
+    /// Returns the maximum number of bytes kept.
+    pub fn cap(&self) -> u64 {
+        self.cap
+    }
+
+    /// Returns the number of bytes currently kept.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if no data is kept.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a reader over the kept data, from oldest to newest.
+    pub fn reader(&mut self) -> Result<Take<&File>, Error> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(HEADER_LEN + self.header.start))?;
+        Ok(file.take(self.len))
+    }
+
+    /// Reads all kept data into a new `Vec`.
+    pub fn read_all(&mut self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.reader()?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Finishes an interrupted compaction and validates the header against the file size.
+    fn recover(&mut self) -> Result<(), Error> {
+        let mut physical = self.file.metadata()?.len().saturating_sub(HEADER_LEN);
+
+        if self.header.end != END_OF_FILE {
+            if self.header.start != 0 || self.header.end > physical {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid capped file header",
+                ));
+            }
+
+            self.file.truncate_u64(HEADER_LEN + self.header.end)?;
+            self.file.sync_data()?;
+            physical = self.header.end;
+
+            self.header.end = END_OF_FILE;
+            self.header.write(&self.file)?;
+        }
+
+        if self.header.start > physical {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "invalid capped file header",
+            ));
+        }
+
+        self.len = physical - self.header.start;
+        Ok(())
+    }
+
+    /// Discards the oldest data if more than `cap` bytes are kept, and reclaims the space.
+    fn discard(&mut self) -> Result<(), Error> {
+        if self.len > self.cap {
+            self.header.start += self.len - self.cap;
+            self.len = self.cap;
+            self.header.write(&self.file)?;
+        }
+
+        if self.collapse && self.collapse_discarded()? {
+            return Ok(());
+        }
+
+        self.compact()
+    }
+
+    /// Removes the discarded data from the start of the file using `FALLOC_FL_COLLAPSE_RANGE`.
+    /// Returns `false` if nothing was collapsed.
+    fn collapse_discarded(&mut self) -> Result<bool, Error> {
+        let block = match file_block_size(&self.file)? {
+            Some(block) => block,
+            None => return Ok(false),
+        };
+        let removed = self.header.start - self.header.start % block;
+        // The collapsed range must be aligned and must not reach the end of the file
+        if removed == 0 || !HEADER_LEN.is_multiple_of(block) || self.len == 0 {
+            return Ok(false);
+        }
+
+        // Update the header first, a crash before the collapse then only exposes old data
+        let previous = self.header;
+        self.header.start -= removed;
+        self.header.write(&self.file)?;
+        self.file.sync_data()?;
+
+        if try_collapse(&self.file, HEADER_LEN, removed)? {
+            // Later header updates must not reach the disk before the collapse
+            self.file.sync_data()?;
+            return Ok(true);
+        }
+
+        self.header = previous;
+        self.header.write(&self.file)?;
+        self.collapse = false;
+        Ok(false)
+    }
+
+    /// Moves the kept data to the start of the file and truncates it, once the data fits into the
+    /// discarded area so that copying never overwrites data the header still points to.
+    fn compact(&mut self) -> Result<(), Error> {
+        let start = self.header.start;
+        if start == 0 || start < self.len {
+            return Ok(());
+        }
+
+        // The header discarding the overwritten data and the copied data have to be on disk
+        // before the header points to the copy
+        self.file.sync_data()?;
+        crate::copy::copy_down(&mut &self.file, HEADER_LEN + start, HEADER_LEN, self.len)?;
+        self.file.sync_data()?;
+
+        self.header = Header {
+            start: 0,
+            end: self.len,
+        };
+        self.header.write(&self.file)?;
+        self.file.sync_data()?;
+
+        self.file.truncate_u64(HEADER_LEN + self.len)?;
+        self.file.sync_data()?;
+
+        self.header.end = END_OF_FILE;
+        self.header.write(&self.file)
+    }
+}
+
+impl Write for CappedFile {
+    /// Appends the data, discarding the oldest data if the cap is exceeded. Writing more than
+    /// `cap` bytes at once only stores the last `cap` bytes.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let skip = buf.len() - cmp::min(self.cap, buf.len() as u64) as usize;
+
+        let mut file = &self.file;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&buf[skip..])?;
+
+        self.len += (buf.len() - skip) as u64;
+        self.discard()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CappedFile, Header, END_OF_FILE, HEADER_LEN};
+    use std::{fs, io::Write, path::Path};
+
+    fn check_cap(dir: &Path, max_physical: u64) {
+        let path = dir.join("capped");
+        let cap = 3 * 4096;
+
+        let mut f = CappedFile::open(&path, cap).unwrap();
+        assert!(f.is_empty());
+
+        let mut expected = Vec::new();
+        for i in 0..20_000u32 {
+            let line = format!("{}\n", i);
+            f.write_all(line.as_bytes()).unwrap();
+            expected.extend_from_slice(line.as_bytes());
+
+            let physical = fs::metadata(&path).unwrap().len();
+            assert!(physical <= max_physical, "{} > {}", physical, max_physical);
+        }
+
+        let kept = &expected[expected.len() - cap as usize..];
+        assert_eq!(f.len(), cap);
+        assert_eq!(f.read_all().unwrap(), kept);
+
+        // Reopening finds the same data
+        drop(f);
+        let mut f = CappedFile::open(&path, cap).unwrap();
+        assert_eq!(f.read_all().unwrap(), kept);
+
+        // Reopening with a smaller cap discards data
+        drop(f);
+        let mut f = CappedFile::open(&path, 10).unwrap();
+        assert_eq!(f.read_all().unwrap(), &kept[kept.len() - 10..]);
+    }
+
+    #[test]
+    fn cap() {
+        // Collapsed on ext4 and XFS, compacted elsewhere
+        let dir = tempfile::tempdir().unwrap();
+        check_cap(dir.path(), HEADER_LEN + 2 * 3 * 4096);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn cap_tmpfs() {
+        // tmpfs doesn't support collapsing ranges
+        if let Ok(dir) = tempfile::tempdir_in("/dev/shm") {
+            check_cap(dir.path(), HEADER_LEN + 2 * 3 * 4096);
+        }
+    }
+
+    #[test]
+    fn large_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut f = CappedFile::open(dir.path().join("capped"), 4).unwrap();
+
+        f.write_all(b"0123456789").unwrap();
+        assert_eq!(f.read_all().unwrap(), b"6789");
+    }
+
+    #[test]
+    fn interrupted_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capped");
+
+        let mut f = CappedFile::open(&path, 16).unwrap();
+        f.write_all(b"0123").unwrap();
+
+        // Data was copied and the header updated, but the file wasn't truncated yet
+        f.file.write_all(b"stale").unwrap();
+        Header { start: 0, end: 4 }.write(&f.file).unwrap();
+        drop(f);
+
+        let mut f = CappedFile::open(&path, 16).unwrap();
+        assert_eq!(f.read_all().unwrap(), b"0123");
+        assert_eq!(f.header.end, END_OF_FILE);
+        assert_eq!(fs::metadata(&path).unwrap().len(), HEADER_LEN + 4);
+    }
+
+    #[test]
+    fn invalid_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capped");
+        fs::write(&path, vec![1; 4096 + 24]).unwrap();
+
+        let e = CappedFile::open(&path, 16).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
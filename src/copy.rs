@@ -0,0 +1,123 @@
+//! Helpers for moving data within a single IO object.
+
+use std::{
+    cmp,
+    fs::File,
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Copies `len` bytes from offset `src` to offset `dst` within the same object.
+///
+/// The data is copied front to back, so this is only correct if `dst <= src`. The position of
+/// the object is left unspecified.
+pub(crate) fn copy_down<T>(io: &mut T, src: u64, dst: u64, len: u64) -> Result<(), Error>
+where
+    T: Read + Write + Seek + ?Sized,
+{
+    debug_assert!(dst <= src);
+
+    let mut buf = vec![0; cmp::min(len, BUF_SIZE as u64) as usize];
+    let mut copied = 0;
+    while copied < len {
+        let n = cmp::min(len - copied, buf.len() as u64) as usize;
+
+        io.seek(SeekFrom::Start(src + copied))?;
+        io.read_exact(&mut buf[..n])?;
+        io.seek(SeekFrom::Start(dst + copied))?;
+        io.write_all(&buf[..n])?;
+
+        copied += n as u64;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) use crate::linux::try_collapse;
+
+/// Collapsing ranges is only supported on Linux, so the data always has to be copied elsewhere.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn try_collapse(_file: &File, _offset: u64, _len: u64) -> Result<bool, Error> {
+    Ok(false)
+}
+
+/// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the file was opened in
+/// append mode.
+///
+/// Every write to such a file goes to its end regardless of the position, even with `pwrite` on
+/// Linux, so data can't be moved within it. The mode can only be detected on Unix.
+pub(crate) fn check_not_append(file: &File) -> Result<(), Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY: The file descriptor is valid for the lifetime of `file`.
+        let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+        if flags == -1 {
+            return Err(Error::last_os_error());
+        }
+        if flags & libc::O_APPEND != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "can't move data within a file opened in append mode",
+            ));
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = file;
+
+    Ok(())
+}
+
+/// Fixtures for the tests of the file impls that move data within the file.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::{
+        fs::{File, OpenOptions},
+        io::{Read, Seek, SeekFrom, Write},
+    };
+    use tempfile::NamedTempFile;
+
+    /// Calls `check` with an empty temporary file. On Linux, it is called again with a file in
+    /// tmpfs, which doesn't support collapsing ranges, so that the fallbacks are covered as well.
+    pub(crate) fn files<F>(mut check: F)
+    where
+        F: FnMut(File),
+    {
+        check(tempfile::tempfile().unwrap());
+
+        #[cfg(target_os = "linux")]
+        if let Ok(f) = tempfile::tempfile_in("/dev/shm") {
+            check(f);
+        }
+    }
+
+    /// Returns `len` bytes that don't repeat with a power of two period.
+    pub(crate) fn data(len: u64) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Reads the whole file from the start.
+    pub(crate) fn read_all(mut file: &File) -> Vec<u8> {
+        let mut buf = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    /// Returns a file containing `0123456789`, and a handle to it opened in append mode.
+    pub(crate) fn append_file() -> (NamedTempFile, File) {
+        let mut named = NamedTempFile::new().unwrap();
+        named.write_all(b"0123456789").unwrap();
+
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(named.path())
+            .unwrap();
+        (named, file)
+    }
+}
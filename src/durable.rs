@@ -0,0 +1,110 @@
+//! Truncation that is persisted to disk before returning.
+
+use std::{fs::File, io::Error, path::Path};
+
+/// How much effort to spend on making a truncation persistent.
+///
+/// Used by [`truncate_durable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Durability<'a> {
+    /// Don't sync at all. The new length may be lost on a crash.
+    None,
+    /// Sync the file data and the metadata required to read it back, including the new length
+    /// (`fdatasync`). See [`File::sync_data`].
+    DataSync,
+    /// Sync the file data and all metadata (`fsync`). See [`File::sync_all`].
+    FullSync,
+    /// Like [`FullSync`](Durability::FullSync), and additionally sync the directory containing the
+    /// file at the given path.
+    ///
+    /// This is required to persist the directory entry of a newly created file. Syncing the
+    /// directory is only supported on Unix platforms, elsewhere it is skipped.
+    FullSyncWithDir(&'a Path),
+}
+
+/// Truncate the file to the given new length and sync it to disk according to `durability`.
+///
+/// The file is truncated with [`File::set_len`], so like the [`Truncate`](crate::Truncate) impl
+/// for [`File`] this extends the file if `new_len` is larger than its current length.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{truncate_durable, Durability};
+/// # use std::io::Write;
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("wal");
+///
+/// let mut f = std::fs::File::create(&path).unwrap();
+/// f.write_all(b"record\ntorn rec").unwrap();
+///
+/// truncate_durable(&f, 7, Durability::FullSyncWithDir(&path)).unwrap();
+/// assert_eq!(f.metadata().unwrap().len(), 7);
+/// ```
+pub fn truncate_durable(
+    file: &File,
+    new_len: u64,
+    durability: Durability<'_>,
+) -> Result<(), Error> {
+    file.set_len(new_len)?;
+
+    match durability {
+        Durability::None => Ok(()),
+        Durability::DataSync => file.sync_data(),
+        Durability::FullSync => file.sync_all(),
+        Durability::FullSyncWithDir(path) => {
+            file.sync_all()?;
+            sync_parent_dir(path)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), Error> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate_durable, Durability};
+    use std::{fs::File, io::Write};
+
+    #[test]
+    fn durabilities() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        let mut f = File::create(&path).unwrap();
+        f.write_all(&[0; 16]).unwrap();
+
+        let levels = [
+            Durability::None,
+            Durability::DataSync,
+            Durability::FullSync,
+            Durability::FullSyncWithDir(&path),
+        ];
+        for (i, durability) in levels.iter().enumerate() {
+            truncate_durable(&f, 8 - i as u64, *durability).unwrap();
+            assert_eq!(f.metadata().unwrap().len(), 8 - i as u64);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let f = tempfile::tempfile().unwrap();
+
+        let path = dir.path().join("missing").join("file");
+        assert!(truncate_durable(&f, 0, Durability::FullSyncWithDir(&path)).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Structured error type for truncation failures.
+
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+/// The reasons a truncation can fail.
+///
+/// This is the [error type](crate::Truncate::Error) of the in-memory implementations, which is
+/// available without `std`. Implementations and functions that work with `io::Error`s wrap a
+/// `TruncateError` in them for the errors they create themselves, which can be retrieved with
+/// `io::Error::get_ref` or converted back using the [`From`] impl.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{Truncate, TruncateError};
+/// let mut v: &[u8] = &[0, 1, 2, 3];
+///
+/// match v.truncate(5) {
+///     Err(TruncateError::GrowNotSupported { requested, current }) => {
+///         assert_eq!((requested, current), (5, 4));
+///     }
+///     _ => unreachable!(),
+/// }
+///
+/// // Wrapped in an io::Error by generic code for files
+/// let e = std::io::Error::from(v.truncate(5).unwrap_err());
+/// assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TruncateError {
+    /// The requested length is larger than the current length, and the object can't grow.
+    GrowNotSupported {
+        /// The requested new length.
+        requested: u64,
+        /// The length of the object at the time of the request.
+        current: u64,
+    },
+    /// The requested length can't be represented by the object on this platform, for example a
+    /// length that doesn't fit into a `usize`.
+    TooLarge {
+        /// The requested new length.
+        requested: u64,
+    },
+    /// The number of bytes to remove is larger than the current length.
+    ExceedsLength {
+        /// The requested number of bytes to remove.
+        requested: u64,
+        /// The length of the object at the time of the request.
+        current: u64,
+    },
+    /// The requested length would split a UTF-8 encoded character of a text buffer.
+    NotCharBoundary {
+        /// The requested new length.
+        requested: u64,
+    },
+    /// The object doesn't support the requested operation.
+    Unsupported,
+    /// An underlying IO error.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl TruncateError {
+    /// Returns the [`io::ErrorKind`] this error is converted to.
+    ///
+    /// [`GrowNotSupported`](TruncateError::GrowNotSupported),
+    /// [`ExceedsLength`](TruncateError::ExceedsLength) and
+    /// [`NotCharBoundary`](TruncateError::NotCharBoundary) map to
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput), the other variants to
+    /// [`Unsupported`](io::ErrorKind::Unsupported) or the kind of the wrapped error.
+    #[cfg(feature = "std")]
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            TruncateError::GrowNotSupported { .. }
+            | TruncateError::ExceedsLength { .. }
+            | TruncateError::NotCharBoundary { .. } => io::ErrorKind::InvalidInput,
+            TruncateError::TooLarge { .. } | TruncateError::Unsupported => {
+                io::ErrorKind::Unsupported
+            }
+            TruncateError::Io(e) => e.kind(),
+        }
+    }
+}
+
+impl fmt::Display for TruncateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TruncateError::GrowNotSupported { requested, current } => write!(
+                f,
+                "tried to truncate to greater length ({} > {})",
+                requested, current
+            ),
+            TruncateError::ExceedsLength { requested, current } => write!(
+                f,
+                "tried to truncate by more than the length ({} > {})",
+                requested, current
+            ),
+            TruncateError::TooLarge { requested } => {
+                write!(f, "length does not fit into usize ({})", requested)
+            }
+            TruncateError::NotCharBoundary { requested } => {
+                write!(f, "length is not on a char boundary ({})", requested)
+            }
+            TruncateError::Unsupported => f.write_str("operation not supported"),
+            #[cfg(feature = "std")]
+            TruncateError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TruncateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            TruncateError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Error for TruncateError {
+    /// Maps the variants to the same kinds as [`kind`](TruncateError::kind).
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            TruncateError::GrowNotSupported { .. }
+            | TruncateError::ExceedsLength { .. }
+            | TruncateError::NotCharBoundary { .. } => embedded_io::ErrorKind::InvalidInput,
+            TruncateError::TooLarge { .. } | TruncateError::Unsupported => {
+                embedded_io::ErrorKind::Unsupported
+            }
+            #[cfg(feature = "std")]
+            TruncateError::Io(e) => e.kind().into(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<TruncateError> for io::Error {
+    /// Wraps the error in an [`io::Error`] of the matching [kind](TruncateError::kind). The
+    /// [`Io`](TruncateError::Io) variant is unwrapped instead.
+    fn from(e: TruncateError) -> Self {
+        match e {
+            TruncateError::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for TruncateError {
+    /// Recovers a `TruncateError` wrapped in the [`io::Error`], or wraps it in the
+    /// [`Io`](TruncateError::Io) variant otherwise.
+    fn from(e: io::Error) -> Self {
+        e.downcast().unwrap_or_else(TruncateError::Io)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::TruncateError;
+    use crate::Truncate;
+    use std::io::{self, ErrorKind};
+
+    #[test]
+    fn roundtrip() {
+        let mut v: Vec<u8> = vec![0, 1, 2, 3];
+        let e = io::Error::from(Truncate::truncate(&mut v, 5).unwrap_err());
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(e.to_string(), "tried to truncate to greater length (5 > 4)");
+
+        match TruncateError::from(e) {
+            TruncateError::GrowNotSupported { requested, current } => {
+                assert_eq!(requested, 5);
+                assert_eq!(current, 4);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn io() {
+        let e = TruncateError::from(io::Error::from(ErrorKind::PermissionDenied));
+        assert!(matches!(e, TruncateError::Io(_)));
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+
+        // Unwrapped again instead of nesting
+        let e = io::Error::from(e);
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+        assert!(e.get_ref().is_none());
+
+        let e = io::Error::from(TruncateError::Unsupported);
+        assert_eq!(e.kind(), ErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn embedded_io() {
+        use embedded_io::{Error, ErrorKind};
+
+        let mut v: &[u8] = &[0, 1];
+        let e = v.truncate(3).unwrap_err();
+        assert_eq!(Error::kind(&e), ErrorKind::InvalidInput);
+        assert_eq!(
+            Error::kind(&TruncateError::Unsupported),
+            ErrorKind::Unsupported
+        );
+        assert_eq!(
+            Error::kind(&TruncateError::from(io::Error::from(
+                io::ErrorKind::PermissionDenied
+            ))),
+            ErrorKind::PermissionDenied
+        );
+    }
+}
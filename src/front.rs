@@ -0,0 +1,234 @@
+//! Truncation at the start of an object.
+
+use crate::{
+    copy::{check_not_append, copy_down, try_collapse},
+    TruncateError,
+};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Cursor, Error, Seek, SeekFrom},
+};
+
+/// A trait for IO objects that can be shortened by removing data at the start.
+///
+/// This is the counterpart to [`Truncate`](crate::Truncate), which removes data at the end.
+pub trait TruncateFront {
+    /// Truncate the object to the given new length in bytes, keeping the last `new_len` bytes and
+    /// discarding everything before them.
+    ///
+    /// The length is a `u64` so that more than 4 GiB of a file can be kept on 32-bit targets. All
+    /// implementations in this crate return an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput) if `new_len` is larger than the current
+    /// length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::TruncateFront;
+    /// let mut v: &[u8] = &[0, 1, 2, 3];
+    /// v.truncate_front(3).unwrap();
+    /// assert_eq!(v, &[1, 2, 3]);
+    /// ```
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error>;
+}
+
+impl TruncateFront for File {
+    /// On Linux, the data is removed using `FALLOC_FL_COLLAPSE_RANGE` if the filesystem supports
+    /// it and the removed length is a multiple of the block size. Otherwise the remaining data is
+    /// copied to the start of the file, followed by a call to [`File::set_len`]. A crash during the
+    /// copy leaves the file at its old length with its start partially overwritten, use
+    /// [`Retain::retain_tail`](crate::Retain::retain_tail) if the operation has to be resumable.
+    ///
+    /// The file position is moved back by the number of removed bytes, or to the start of the file
+    /// if it lies in the removed area.
+    ///
+    /// On Unix, files opened in append mode fail with an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput), since every write to them ends up at the
+    /// end of the file instead of its start.
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        let len = self.metadata()?.len();
+        if new_len > len {
+            return Err(TruncateError::GrowNotSupported {
+                requested: new_len,
+                current: len,
+            }
+            .into());
+        }
+
+        let removed = len - new_len;
+        if removed == 0 {
+            return Ok(());
+        }
+        check_not_append(self)?;
+
+        let mut file: &File = self;
+        let position = file.stream_position()?;
+
+        if !try_collapse(file, 0, removed)? {
+            copy_down(&mut file, removed, 0, new_len)?;
+            file.set_len(new_len)?;
+        }
+
+        file.seek(SeekFrom::Start(position.saturating_sub(removed)))?;
+        Ok(())
+    }
+}
+
+/// Returns the number of bytes to remove from the start of an in-memory buffer of length `len`.
+fn removed_len(new_len: u64, len: usize) -> Result<usize, TruncateError> {
+    let len = len as u64;
+    if new_len > len {
+        return Err(TruncateError::GrowNotSupported {
+            requested: new_len,
+            current: len,
+        });
+    }
+    Ok((len - new_len) as usize)
+}
+
+impl TruncateFront for Vec<u8> {
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        let removed = removed_len(new_len, self.len())?;
+        self.drain(..removed);
+        Ok(())
+    }
+}
+
+impl TruncateFront for VecDeque<u8> {
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        let removed = removed_len(new_len, self.len())?;
+        self.drain(..removed);
+        Ok(())
+    }
+}
+
+impl TruncateFront for &[u8] {
+    /// Shortens the slice by moving its start forward.
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        let removed = removed_len(new_len, self.len())?;
+        *self = &self[removed..];
+        Ok(())
+    }
+}
+
+impl<T> TruncateFront for Cursor<T>
+where
+    T: TruncateFront + crate::Len,
+    Error: From<T::Error>,
+{
+    /// Delegates to the contained [`TruncateFront`] impl. The cursor is moved back by the number of
+    /// removed bytes, so that it keeps pointing at the same data, or to the start if it lies in
+    /// the removed area.
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        let len = self.get_ref().len()?;
+        self.get_mut().truncate_front(new_len)?;
+
+        let removed = len.saturating_sub(new_len);
+        self.set_position(self.position().saturating_sub(removed));
+        Ok(())
+    }
+}
+
+impl<T> TruncateFront for &mut T
+where
+    T: TruncateFront,
+{
+    fn truncate_front(&mut self, new_len: u64) -> Result<(), Error> {
+        (**self).truncate_front(new_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TruncateFront;
+    use crate::copy::fixtures;
+    use std::{
+        collections::VecDeque,
+        fs,
+        io::{Cursor, ErrorKind, Seek, Write},
+    };
+
+    #[test]
+    fn vec() {
+        let mut v: Vec<u8> = vec![0, 1, 2, 3];
+
+        v.truncate_front(3).unwrap();
+        assert_eq!(v, &[1, 2, 3]);
+
+        // Error
+        let e = v.truncate_front(4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn vec_deque() {
+        let mut v: VecDeque<u8> = vec![0, 1, 2, 3].into();
+
+        // Need to call like this in order to not conflict with the unstable inherent method.
+        TruncateFront::truncate_front(&mut v, 1).unwrap();
+        assert_eq!(v, &[3]);
+
+        // Error
+        let e = TruncateFront::truncate_front(&mut v, 2).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn slice() {
+        let mut v: &[u8] = &[0, 1, 2, 3];
+
+        v.truncate_front(2).unwrap();
+        assert_eq!(v, &[2, 3]);
+
+        // Error
+        let e = v.truncate_front(3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3]);
+
+        v.set_position(3);
+        v.truncate_front(2).unwrap();
+        assert_eq!(v.get_ref(), &[2, 3]);
+        assert_eq!(v.position(), 1);
+
+        v.truncate_front(0).unwrap();
+        assert_eq!(v.position(), 0);
+    }
+
+    #[test]
+    fn file() {
+        fixtures::files(|mut f| {
+            let block = 4096;
+            let data = fixtures::data(block * 3);
+            f.write_all(&data).unwrap();
+
+            // Not block aligned, always uses the fallback
+            f.truncate_front(block * 3 - 1).unwrap();
+            assert_eq!(f.stream_position().unwrap(), block * 3 - 1);
+
+            // Block aligned, collapsed if the filesystem supports it
+            f.truncate_front(block * 2 - 1).unwrap();
+            assert_eq!(f.stream_position().unwrap(), block * 2 - 1);
+
+            assert!(fixtures::read_all(&f) == data[block as usize + 1..]);
+
+            // Error
+            let e = f.truncate_front(block * 2).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_append() {
+        let (named, mut f) = fixtures::append_file();
+
+        let e = f.truncate_front(5).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(fs::read(named.path()).unwrap(), b"0123456789");
+    }
+}
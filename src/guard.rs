@@ -0,0 +1,248 @@
+//! Guard that truncates back to a savepoint when dropped.
+
+use crate::{Len, Truncate};
+use std::{
+    fmt,
+    io::{Error, Seek, SeekFrom},
+    ops::{Deref, DerefMut},
+};
+
+/// Restores the position of the inner object.
+type RestorePosition<T> = fn(&mut T, u64) -> Result<(), <T as Truncate>::Error>;
+
+/// A guard that truncates an object back to its original length when dropped.
+///
+/// The length of the object is recorded when the guard is created. Unless the guard is
+/// [committed](TruncateGuard::commit), the object is truncated back to that length when the guard
+/// is dropped, undoing any data appended in the meantime. This is useful to clean up after partial
+/// writes that failed halfway.
+///
+/// Errors during the truncation in the `Drop` impl are ignored. Use
+/// [`rollback`](TruncateGuard::rollback) to handle them.
+///
+/// The guard dereferences to the wrapped object. Since [`Truncate`] and [`Len`] are implemented
+/// for mutable references, it can wrap either an owned object or a `&mut` reference to one.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::TruncateGuard;
+/// # use std::io::Write;
+/// let mut v: Vec<u8> = vec![0, 1];
+///
+/// let mut guard = TruncateGuard::new(&mut v).unwrap();
+/// guard.write_all(&[2, 3]).unwrap();
+/// drop(guard);
+/// assert_eq!(v, &[0, 1]);
+///
+/// let mut guard = TruncateGuard::new(&mut v).unwrap();
+/// guard.write_all(&[2, 3]).unwrap();
+/// guard.commit();
+/// assert_eq!(v, &[0, 1, 2, 3]);
+/// ```
+pub struct TruncateGuard<T>
+where
+    T: Truncate,
+{
+    inner: Option<T>,
+    len: u64,
+    position: Option<(u64, RestorePosition<T>)>,
+}
+
+impl<T> TruncateGuard<T>
+where
+    T: Truncate,
+{
+    /// Creates a new guard, recording the current length of `inner`.
+    pub fn new(inner: T) -> Result<Self, <T as Len>::Error>
+    where
+        T: Len,
+    {
+        Ok(TruncateGuard {
+            len: inner.len()?,
+            inner: Some(inner),
+            position: None,
+        })
+    }
+
+    /// Creates a new guard, recording the current length and seek position of `inner`.
+    ///
+    /// When rolling back, the seek position is restored after truncating.
+    pub fn with_position(mut inner: T) -> Result<Self, Error>
+    where
+        T: Len + Seek,
+        Error: From<<T as Len>::Error>,
+        <T as Truncate>::Error: From<Error>,
+    {
+        fn restore<T>(inner: &mut T, position: u64) -> Result<(), <T as Truncate>::Error>
+        where
+            T: Truncate + Seek,
+            <T as Truncate>::Error: From<Error>,
+        {
+            inner.seek(SeekFrom::Start(position))?;
+            Ok(())
+        }
+
+        Ok(TruncateGuard {
+            len: inner.len()?,
+            position: Some((inner.stream_position()?, restore::<T>)),
+            inner: Some(inner),
+        })
+    }
+
+    /// Returns the length the object will be truncated to.
+    pub fn savepoint(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns a reference to the wrapped object.
+    pub fn get_ref(&self) -> &T {
+        self.inner.as_ref().expect("guard already consumed")
+    }
+
+    /// Returns a mutable reference to the wrapped object.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.as_mut().expect("guard already consumed")
+    }
+
+    /// Keeps all changes made to the object and returns it, without truncating.
+    pub fn commit(mut self) -> T {
+        self.inner.take().expect("guard already consumed")
+    }
+
+    /// Truncates the object back to the recorded length and returns it.
+    ///
+    /// This is the same as dropping the guard, except that errors are returned.
+    pub fn rollback(mut self) -> Result<T, T::Error> {
+        self.restore()?;
+        Ok(self.inner.take().expect("guard already consumed"))
+    }
+
+    fn restore(&mut self) -> Result<(), T::Error> {
+        if let Some(inner) = &mut self.inner {
+            inner.truncate_u64(self.len)?;
+
+            if let Some((position, restore)) = self.position {
+                restore(inner, position)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Drop for TruncateGuard<T>
+where
+    T: Truncate,
+{
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+impl<T> Deref for TruncateGuard<T>
+where
+    T: Truncate,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get_ref()
+    }
+}
+
+impl<T> DerefMut for TruncateGuard<T>
+where
+    T: Truncate,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T> fmt::Debug for TruncateGuard<T>
+where
+    T: Truncate + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TruncateGuard")
+            .field("inner", &self.inner)
+            .field("len", &self.len)
+            .field("position", &self.position.map(|(position, _)| position))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TruncateGuard;
+    use crate::Len;
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn vec() {
+        let mut v: Vec<u8> = vec![0, 1];
+
+        {
+            let mut guard = TruncateGuard::new(&mut v).unwrap();
+            guard.extend_from_slice(&[2, 3]);
+            assert_eq!(guard.savepoint(), 2);
+        }
+        assert_eq!(v, &[0, 1]);
+
+        let mut guard = TruncateGuard::new(v).unwrap();
+        guard.push(2);
+        let v = guard.commit();
+        assert_eq!(v, &[0, 1, 2]);
+
+        let mut guard = TruncateGuard::new(v).unwrap();
+        guard.push(3);
+        let v = guard.rollback().unwrap();
+        assert_eq!(v, &[0, 1, 2]);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut c: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3]);
+        c.set_position(1);
+
+        {
+            let mut guard = TruncateGuard::with_position(&mut c).unwrap();
+            guard.seek(SeekFrom::End(0)).unwrap();
+            guard.write_all(&[4, 5]).unwrap();
+        }
+        assert_eq!(c.get_ref(), &[0, 1, 2, 3]);
+        assert_eq!(c.position(), 1);
+
+        // Without restoring the position, it is clamped to the new end
+        {
+            let mut guard = TruncateGuard::new(&mut c).unwrap();
+            guard.seek(SeekFrom::End(0)).unwrap();
+            guard.write_all(&[4, 5]).unwrap();
+        }
+        assert_eq!(c.get_ref(), &[0, 1, 2, 3]);
+        assert_eq!(c.position(), 4);
+    }
+
+    #[test]
+    fn file() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2, 3]).unwrap();
+
+        {
+            let mut guard = TruncateGuard::with_position(&mut f).unwrap();
+            guard.write_all(&[4, 5]).unwrap();
+        }
+        assert_eq!(Len::len(&f).unwrap(), 4);
+        assert_eq!(f.stream_position().unwrap(), 4);
+
+        let mut guard = TruncateGuard::with_position(&mut f).unwrap();
+        guard.write_all(&[4, 5]).unwrap();
+        guard.commit();
+
+        let mut buf = Vec::new();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, &[0, 1, 2, 3, 4, 5]);
+    }
+}
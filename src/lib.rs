@@ -1,11 +1,137 @@
 //! IO objects that can be shortened.
 //!
 //! See the [`Truncate`] trait.
+#![cfg_attr(
+    feature = "std",
+    doc = "The [`Resize`] trait additionally allows growing objects with an explicit \
+           [`GrowPolicy`], [`TruncateFront`] removes data at the start instead of the end, and \
+           [`RemoveRange`] removes it anywhere in between."
+)]
+//!
+//! # `no_std` support
+//!
+//! The [`Truncate`] and [`Len`] traits, [`TruncateError`], [`SizeSpec`] and the impls for slices
+//! are available without the standard library by disabling the default `std` feature. The `alloc`
+//! feature adds the impls for `Vec<u8>` and the other types of the `alloc` crate. Everything else,
+//! including the impls for files and `std::io::Cursor`, requires `std`.
+//!
+//! # Optional features
+//!
+//! - `std` (default): Everything that requires the standard library, including the `alloc` impls.
+//! - `alloc`: Impls for `Vec<u8>`, `VecDeque<u8>`, `String`, `Box<T>` and `Cow<[u8]>`.
+//! - `tokio`: The `AsyncTruncate` trait, with an impl for `tokio::fs::File`.
+//! - `futures-io`: The `AsyncTruncate` trait without any runtime-specific impls.
+//! - `async-std`: Like `futures-io`, with an impl for `async_std::fs::File`.
+//! - `fallocate`: The Linux-only `FileSpace` extension trait for `fallocate(2)` operations.
+//! - `bytes`, `smallvec`, `arrayvec`, `tinyvec`, `heapless`: [`Truncate`] and [`Len`] impls for
+//!   the byte buffer types of these crates. These don't require `std`.
+//! - `unicode-segmentation`: `Utf8Boundary::Grapheme` for truncating text to whole grapheme
+//!   clusters.
+//! - `embedded-io`: An `embedded_io::Error` impl for [`TruncateError`].
+//! - `serde`: `Serialize` and `Deserialize` impls for [`SizeSpec`].
+//! - `cli`: The `truncate` command-line tool.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod aligned;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_truncate;
+#[cfg(feature = "std")]
+mod buffered;
+#[cfg(any(
+    feature = "bytes",
+    feature = "smallvec",
+    feature = "arrayvec",
+    feature = "tinyvec",
+    feature = "heapless"
+))]
+mod buffers;
+#[cfg(feature = "std")]
+mod capped;
+#[cfg(feature = "std")]
+mod copy;
+#[cfg(feature = "std")]
+mod durable;
+mod error;
+#[cfg(feature = "std")]
+mod front;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod linux;
+#[cfg(feature = "std")]
+mod path;
+#[cfg(feature = "std")]
+mod recover;
+#[cfg(feature = "std")]
+mod remove;
+#[cfg(feature = "std")]
+mod resize;
+#[cfg(feature = "std")]
+mod retain;
+#[cfg(feature = "std")]
+mod rotate;
+#[cfg(feature = "std")]
+mod savepoint;
+mod size;
+#[cfg(all(feature = "fallocate", target_os = "linux"))]
+mod space;
+#[cfg(feature = "std")]
+mod trim;
+#[cfg(feature = "std")]
+mod utf8;
 
+#[cfg(feature = "std")]
+pub use crate::aligned::{Align, TruncateAligned};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use crate::async_truncate::{AsyncTruncate, AsyncTruncateExt, TruncateFuture};
+#[cfg(feature = "std")]
+pub use crate::capped::CappedFile;
+#[cfg(feature = "std")]
+pub use crate::durable::{truncate_durable, Durability};
+pub use crate::error::TruncateError;
+#[cfg(feature = "std")]
+pub use crate::front::TruncateFront;
+#[cfg(feature = "std")]
+pub use crate::guard::TruncateGuard;
+#[cfg(all(feature = "std", unix))]
+pub use crate::path::truncate_at;
+#[cfg(feature = "std")]
+pub use crate::path::{truncate_path, TruncateOptions};
+#[cfg(feature = "std")]
+pub use crate::recover::{
+    recover_tail, Endian, JsonLines, LengthPrefixed, Lines, PrefixWidth, RecordFormat,
+};
+#[cfg(feature = "std")]
+pub use crate::remove::RemoveRange;
+#[cfg(feature = "std")]
+pub use crate::resize::{GrowPolicy, Resize};
+#[cfg(feature = "std")]
+pub use crate::retain::Retain;
+#[cfg(feature = "std")]
+pub use crate::rotate::{RotatingWriter, Rotation};
+#[cfg(feature = "std")]
+pub use crate::savepoint::{SavepointId, Savepoints};
+pub use crate::size::{ParseSizeError, SizeSpec};
+#[cfg(all(feature = "fallocate", target_os = "linux"))]
+pub use crate::space::FileSpace;
+#[cfg(feature = "std")]
+pub use crate::trim::TrimTrailing;
+#[cfg(feature = "std")]
+pub use crate::utf8::{TruncateUtf8, Utf8Boundary};
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use core::{convert::TryFrom, mem};
+#[cfg(feature = "std")]
 use std::{
     cmp,
     fs::File,
-    io::{Cursor, Error, ErrorKind},
+    io::{self, Cursor},
 };
 
 /// A trait for IO objects that can be shortened.
@@ -13,6 +139,13 @@ use std::{
 /// See the documentation comments on individual implementations for some potentially important
 /// notes on their specific behaviors.
 pub trait Truncate {
+    /// The error returned when truncating fails.
+    ///
+    /// This is `std::io::Error` for files and [`TruncateError`] for in-memory buffers, which
+    /// converts into an `io::Error`. Implementations need to be able to represent the errors
+    /// produced by the provided methods.
+    type Error: From<TruncateError>;
+
     /// Truncate the object to the given new length in bytes.
     ///
     /// The behavior when `new_len` is larger than the current length of the object is unspecified.
@@ -26,82 +159,391 @@ pub trait Truncate {
     /// v.truncate(3).unwrap();
     /// assert_eq!(v, &[0, 1, 2]);
     /// ```
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error>;
+    fn truncate(&mut self, new_len: usize) -> Result<(), Self::Error>;
+
+    /// Truncate the object to the given new length in bytes, given as a `u64`.
+    ///
+    /// This allows truncating objects such as files whose length may not fit into a `usize` on
+    /// 32-bit targets. The default implementation converts `new_len` to a `usize` and calls
+    /// [`truncate`](Truncate::truncate). If the conversion fails, a
+    /// [`TruncateError::TooLarge`] is returned, which is distinct from the
+    /// [`TruncateError::GrowNotSupported`] used by the in-memory implementations for lengths that
+    /// are too large.
+    ///
+    /// Implementations that can handle 64-bit lengths natively should override this method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Truncate;
+    /// let mut f = tempfile::tempfile().unwrap();
+    /// f.truncate_u64(5 * 1024 * 1024 * 1024).unwrap();
+    /// assert_eq!(f.metadata().unwrap().len(), 5 * 1024 * 1024 * 1024);
+    /// ```
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), Self::Error> {
+        self.truncate(usize_len(new_len)?)
+    }
+
+    /// Shorten the object by the given number of bytes.
+    ///
+    /// Returns a [`TruncateError::ExceedsLength`] if `n` is larger than the current length of the
+    /// object.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Truncate;
+    /// let mut v: &[u8] = &[0, 1, 2, 3];
+    /// v.truncate_by(3).unwrap();
+    /// assert_eq!(v, &[0]);
+    /// ```
+    fn truncate_by(&mut self, n: u64) -> Result<(), <Self as Truncate>::Error>
+    where
+        Self: Len<Error = <Self as Truncate>::Error> + Sized,
+    {
+        let len = Len::len(self)?;
+        let new_len = len.checked_sub(n).ok_or(TruncateError::ExceedsLength {
+            requested: n,
+            current: len,
+        })?;
+
+        self.truncate_u64(new_len)
+    }
+
+    /// Truncate the object to a length of zero.
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        self.truncate(0)
+    }
+}
+
+/// Converts a 64-bit length into a `usize`, for use by in-memory implementations.
+fn usize_len(len: u64) -> Result<usize, TruncateError> {
+    usize::try_from(len).map_err(|_| TruncateError::TooLarge { requested: len })
+}
+
+/// Returns an error if `new_len` would grow an object that can only be shortened.
+fn check_len(new_len: usize, len: usize) -> Result<(), TruncateError> {
+    if new_len <= len {
+        Ok(())
+    } else {
+        Err(TruncateError::GrowNotSupported {
+            requested: new_len as u64,
+            current: len as u64,
+        })
+    }
+}
+
+/// A trait for IO objects whose length can be queried.
+///
+/// This is the companion to [`Truncate`], allowing generic code to compute and validate new
+/// lengths.
+pub trait Len {
+    /// The error returned when querying the length fails.
+    ///
+    /// The impls in this crate use the same error type as their [`Truncate`] impls.
+    type Error;
+
+    /// Returns the current length of the object in bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Len;
+    /// let v: &[u8] = &[0, 1, 2, 3];
+    /// assert_eq!(Len::len(&v).unwrap(), 4);
+    /// ```
+    fn len(&self) -> Result<u64, Self::Error>;
+
+    /// Returns `true` if the object has a length of zero.
+    fn is_empty(&self) -> Result<bool, Self::Error> {
+        Ok(self.len()? == 0)
+    }
 }
 
+#[cfg(feature = "std")]
 impl Truncate for File {
+    type Error = io::Error;
+
+    /// Delegates to [`File::set_len`].
+    fn truncate(&mut self, new_len: usize) -> Result<(), io::Error> {
+        self.set_len(new_len as u64)
+    }
+
     /// Delegates to [`File::set_len`].
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), io::Error> {
+        self.set_len(new_len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Truncate for &File {
+    type Error = io::Error;
+
+    /// Delegates to [`File::set_len`], which only requires a shared reference.
+    fn truncate(&mut self, new_len: usize) -> Result<(), io::Error> {
         self.set_len(new_len as u64)
     }
+
+    /// Delegates to [`File::set_len`], which only requires a shared reference.
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), io::Error> {
+        self.set_len(new_len)
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl Truncate for Vec<u8> {
+    type Error = TruncateError;
+
     /// Shortens the `Vec` or returns an error if the length would be larger than the current
     /// length.
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
-        if new_len <= self.len() {
-            self.truncate(new_len);
-            Ok(())
-        } else {
-            Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!(
-                    "tried to truncate to greater length ({} > {})",
-                    new_len,
-                    self.len()
-                ),
-            ))
-        }
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        check_len(new_len, Vec::len(self))?;
+        self.truncate(new_len);
+        Ok(())
     }
 }
 
-impl<'a> Truncate for &'a [u8] {
+impl Truncate for &[u8] {
+    type Error = TruncateError;
+
     /// Shortens the slice or returns and error if the length would be larger than the current
     /// length.
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
-        if new_len <= self.len() {
-            *self = &self[..new_len];
-            Ok(())
-        } else {
-            Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!(
-                    "tried to truncate to greater length ({} > {})",
-                    new_len,
-                    self.len()
-                ),
-            ))
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        check_len(new_len, <[u8]>::len(self))?;
+        *self = &self[..new_len];
+        Ok(())
+    }
+}
+
+impl Truncate for &mut [u8] {
+    type Error = TruncateError;
+
+    /// Shortens the slice or returns an error if the length would be larger than the current
+    /// length.
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        check_len(new_len, <[u8]>::len(self))?;
+        *self = &mut mem::take(self)[..new_len];
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Truncate for VecDeque<u8> {
+    type Error = TruncateError;
+
+    /// Shortens the `VecDeque` by removing bytes from the back, or returns an error if the length
+    /// would be larger than the current length.
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        check_len(new_len, VecDeque::len(self))?;
+        VecDeque::truncate(self, new_len);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Truncate for Cow<'_, [u8]> {
+    type Error = TruncateError;
+
+    /// Reslices a borrowed slice and shortens an owned `Vec`, without copying the data either
+    /// way. Returns an error if the length would be larger than the current length.
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        match self {
+            Cow::Borrowed(slice) => Truncate::truncate(slice, new_len),
+            Cow::Owned(vec) => Truncate::truncate(vec, new_len),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Truncate for String {
+    type Error = TruncateError;
+
+    /// Shortens the string or returns an error if the length would be larger than the current
+    /// length or doesn't lie on a char boundary. See `TruncateUtf8` for rounding the length
+    /// down to a boundary instead.
+    fn truncate(&mut self, new_len: usize) -> Result<(), TruncateError> {
+        check_len(new_len, String::len(self))?;
+        if !self.is_char_boundary(new_len) {
+            return Err(TruncateError::NotCharBoundary {
+                requested: new_len as u64,
+            });
         }
+
+        self.truncate(new_len);
+        Ok(())
     }
 }
 
+#[cfg(feature = "std")]
 impl<T> Truncate for Cursor<T>
 where
     T: Truncate,
 {
+    type Error = T::Error;
+
     /// Delegates to the contained [`Truncate`] impl. The cursor will be moved to the end of the
     /// data if it lies in the truncated area.
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
+    fn truncate(&mut self, new_len: usize) -> Result<(), T::Error> {
         self.get_mut().truncate(new_len)?;
         self.set_position(cmp::min(new_len as u64, self.position()));
         Ok(())
     }
+
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), T::Error> {
+        self.get_mut().truncate_u64(new_len)?;
+        self.set_position(cmp::min(new_len, self.position()));
+        Ok(())
+    }
 }
 
 impl<T> Truncate for &mut T
 where
     T: Truncate,
 {
-    fn truncate(&mut self, new_len: usize) -> Result<(), Error> {
+    type Error = T::Error;
+
+    fn truncate(&mut self, new_len: usize) -> Result<(), T::Error> {
+        (**self).truncate(new_len)
+    }
+
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), T::Error> {
+        (**self).truncate_u64(new_len)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Truncate for Box<T>
+where
+    T: Truncate + ?Sized,
+{
+    type Error = T::Error;
+
+    fn truncate(&mut self, new_len: usize) -> Result<(), T::Error> {
         (**self).truncate(new_len)
     }
+
+    fn truncate_u64(&mut self, new_len: u64) -> Result<(), T::Error> {
+        (**self).truncate_u64(new_len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Len for File {
+    type Error = io::Error;
+
+    /// Queries the length from the file [metadata](File::metadata).
+    fn len(&self) -> Result<u64, io::Error> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Len for &File {
+    type Error = io::Error;
+
+    /// Queries the length from the file [metadata](File::metadata).
+    fn len(&self) -> Result<u64, io::Error> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Len for Vec<u8> {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(Vec::len(self) as u64)
+    }
+}
+
+impl Len for &[u8] {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+}
+
+impl Len for &mut [u8] {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Len for VecDeque<u8> {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(VecDeque::len(self) as u64)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Len for Cow<'_, [u8]> {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Len for String {
+    type Error = TruncateError;
+
+    fn len(&self) -> Result<u64, TruncateError> {
+        Ok(String::len(self) as u64)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Len for Cursor<T>
+where
+    T: Len,
+{
+    type Error = T::Error;
+
+    /// Returns the length of the contained data, regardless of the cursor position.
+    fn len(&self) -> Result<u64, T::Error> {
+        self.get_ref().len()
+    }
+}
+
+impl<T> Len for &mut T
+where
+    T: Len,
+{
+    type Error = T::Error;
+
+    fn len(&self) -> Result<u64, T::Error> {
+        (**self).len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Len for Box<T>
+where
+    T: Len + ?Sized,
+{
+    type Error = T::Error;
+
+    fn len(&self) -> Result<u64, T::Error> {
+        (**self).len()
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use super::*;
-    use std::io::{Seek, SeekFrom, Write};
+    use super::{Len, Truncate, TruncateError};
+    use std::{
+        borrow::Cow,
+        collections::VecDeque,
+        fs::File,
+        io::{Cursor, ErrorKind, Seek, SeekFrom, Write},
+    };
 
     #[test]
     fn vec() {
@@ -128,6 +570,65 @@ mod tests {
         assert_eq!(e.kind(), ErrorKind::InvalidInput);
     }
 
+    #[test]
+    fn mut_slice() {
+        let mut data = [0, 1, 2, 3];
+        let mut v: &mut [u8] = &mut data;
+
+        v.truncate(3).unwrap();
+        v[0] = 4;
+        assert_eq!(v, &[4, 1, 2]);
+
+        // Error
+        let e = v.truncate(4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(data, [4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn vec_deque() {
+        let mut v: VecDeque<u8> = (0..4).collect();
+        v.rotate_left(1);
+
+        Truncate::truncate(&mut v, 3).unwrap();
+        assert_eq!(v, &[1, 2, 3]);
+        assert_eq!(Len::len(&v).unwrap(), 3);
+
+        // Error
+        let e = Truncate::truncate(&mut v, 4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cow() {
+        let data = [0, 1, 2, 3];
+        let mut v = Cow::Borrowed(&data[..]);
+        v.truncate(3).unwrap();
+        assert!(matches!(v, Cow::Borrowed(&[0, 1, 2])));
+
+        let mut v: Cow<'_, [u8]> = Cow::Owned(vec![0, 1, 2, 3]);
+        v.truncate(1).unwrap();
+        assert_eq!(Len::len(&v).unwrap(), 1);
+        assert!(matches!(v, Cow::Owned(_)));
+
+        // Error
+        let e = v.truncate(2).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn boxed() {
+        let mut v: Box<Vec<u8>> = Box::new(vec![0, 1, 2, 3]);
+        v.truncate_u64(2).unwrap();
+        assert_eq!(*v, &[0, 1]);
+
+        let mut v: Box<dyn Truncate<Error = TruncateError>> =
+            Box::new(Cursor::new(vec![0, 1, 2, 3]));
+        v.truncate(2).unwrap();
+        let e = v.truncate(3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn cursor() {
         let mut v: Cursor<&[u8]> = Cursor::new(&[0, 1, 2, 3]);
@@ -153,4 +654,80 @@ mod tests {
 
         // File::set_len works with longer values too
     }
+
+    #[test]
+    fn file_ref() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2, 3]).unwrap();
+
+        let mut r: &File = &f;
+        r.truncate(3).unwrap();
+        assert_eq!(Len::len(&r).unwrap(), 3);
+        r.truncate_u64(5).unwrap();
+        assert_eq!(Len::len(&f).unwrap(), 5);
+    }
+
+    #[test]
+    fn len() {
+        let v: Vec<u8> = vec![0, 1, 2, 3];
+        assert_eq!(Len::len(&v).unwrap(), 4);
+        assert!(!Len::is_empty(&v).unwrap());
+
+        let c = Cursor::new(&v[..0]);
+        assert_eq!(c.len().unwrap(), 0);
+        assert!(c.is_empty().unwrap());
+
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2]).unwrap();
+        assert_eq!(Len::len(&f).unwrap(), 3);
+    }
+
+    #[test]
+    fn truncate_by() {
+        let mut v: Vec<u8> = vec![0, 1, 2, 3];
+        v.truncate_by(1).unwrap();
+        assert_eq!(v, &[0, 1, 2]);
+
+        // Error
+        let e = v.truncate_by(4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2, 3]).unwrap();
+        f.truncate_by(2).unwrap();
+        assert_eq!(Len::len(&f).unwrap(), 2);
+    }
+
+    #[test]
+    fn truncate_u64() {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3]);
+        v.set_position(4);
+        v.truncate_u64(2).unwrap();
+        assert_eq!(v.get_ref(), &[0, 1]);
+        assert_eq!(v.position(), 2);
+
+        // Error
+        let e = v.truncate_u64(3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        #[cfg(target_pointer_width = "32")]
+        {
+            let e = v.truncate_u64(u64::from(u32::MAX) + 1).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Unsupported);
+        }
+
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2, 3]).unwrap();
+        f.truncate_u64(1).unwrap();
+        assert_eq!(Len::len(&f).unwrap(), 1);
+    }
+
+    #[test]
+    fn clear() {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3]);
+        v.set_position(2);
+        v.clear().unwrap();
+        assert!(v.get_ref().is_empty());
+        assert_eq!(v.position(), 0);
+    }
 }
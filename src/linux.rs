@@ -0,0 +1,146 @@
+//! Linux-specific file operations.
+
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{Error, ErrorKind},
+    os::unix::io::AsRawFd,
+};
+
+#[cfg(target_env = "musl")]
+use libc::{fallocate, lseek, off_t};
+#[cfg(not(target_env = "musl"))]
+use libc::{fallocate64 as fallocate, lseek64 as lseek, off64_t as off_t};
+
+/// Safe wrapper around `fallocate(2)`.
+///
+/// Filesystems that don't support the requested `mode` return an error of kind
+/// [`Unsupported`](ErrorKind::Unsupported).
+pub(crate) fn fallocate_file(
+    file: &File,
+    mode: libc::c_int,
+    offset: u64,
+    len: u64,
+) -> Result<(), Error> {
+    let offset = i64::try_from(offset).map_err(|_| ErrorKind::InvalidInput)?;
+    let len = i64::try_from(len).map_err(|_| ErrorKind::InvalidInput)?;
+
+    loop {
+        // SAFETY: The file descriptor is valid for the lifetime of `file`.
+        let res = unsafe { fallocate(file.as_raw_fd(), mode, offset, len) };
+        if res == 0 {
+            return Ok(());
+        }
+
+        let e = Error::last_os_error();
+        if e.kind() != ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Removes `len` bytes starting at `offset` from the file using `FALLOC_FL_COLLAPSE_RANGE`.
+///
+/// Both values have to be multiples of the filesystem block size, and the range must end before
+/// the end of the file.
+pub(crate) fn collapse_range(file: &File, offset: u64, len: u64) -> Result<(), Error> {
+    fallocate_file(file, libc::FALLOC_FL_COLLAPSE_RANGE, offset, len)
+}
+
+/// Removes `len` bytes starting at `offset` from the file with [`collapse_range`] if possible.
+///
+/// Returns `false` without modifying the file if the range isn't aligned to the block size,
+/// reaches the end of the file, or if the filesystem doesn't support collapsing ranges.
+pub(crate) fn try_collapse(file: &File, offset: u64, len: u64) -> Result<bool, Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = file.metadata()?;
+    let block = metadata.blksize();
+    if len == 0
+        || !offset.is_multiple_of(block)
+        || !len.is_multiple_of(block)
+        || offset.saturating_add(len) >= metadata.len()
+    {
+        return Ok(false);
+    }
+
+    match collapse_range(file, offset, len) {
+        Ok(()) => Ok(true),
+        Err(e) if is_unsupported(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns `true` if the error indicates that an `fallocate` mode can't be used for the given file
+/// or range, so that a portable fallback should be used instead.
+pub(crate) fn is_unsupported(e: &Error) -> bool {
+    matches!(e.kind(), ErrorKind::Unsupported | ErrorKind::InvalidInput)
+}
+
+fn seek(file: &File, offset: u64, whence: libc::c_int) -> Result<Option<u64>, Error> {
+    let offset = off_t::try_from(offset).map_err(|_| ErrorKind::InvalidInput)?;
+
+    // SAFETY: The file descriptor is valid for the lifetime of `file`.
+    let res = unsafe { lseek(file.as_raw_fd(), offset, whence) };
+    if res >= 0 {
+        return Ok(Some(res as u64));
+    }
+
+    let e = Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ENXIO) => Ok(None),
+        _ => Err(e),
+    }
+}
+
+/// Returns the start of the next region containing data at or after `offset` using `SEEK_DATA`,
+/// or `None` if there is no more data.
+///
+/// Filesystems without support for sparse files treat the whole file as data.
+pub(crate) fn seek_data(file: &File, offset: u64) -> Result<Option<u64>, Error> {
+    seek(file, offset, libc::SEEK_DATA)
+}
+
+/// Returns the start of the next hole at or after `offset` using `SEEK_HOLE`. The end of the file
+/// counts as a hole.
+pub(crate) fn seek_hole(file: &File, offset: u64) -> Result<u64, Error> {
+    seek(file, offset, libc::SEEK_HOLE)?.ok_or_else(|| ErrorKind::InvalidInput.into())
+}
+
+/// Copies `len` bytes from offset `src` to offset `dst` within the file using
+/// `copy_file_range(2)`, without passing the data through userspace.
+///
+/// The ranges must not overlap. Filesystems and kernels that can't copy within the file return
+/// an error for which [`is_unsupported`] returns `true`, possibly after copying a part of the data.
+pub(crate) fn copy_file_range(file: &File, src: u64, dst: u64, len: u64) -> Result<(), Error> {
+    let mut src = libc::loff_t::try_from(src).map_err(|_| ErrorKind::InvalidInput)?;
+    let mut dst = libc::loff_t::try_from(dst).map_err(|_| ErrorKind::InvalidInput)?;
+    let mut remaining = len;
+
+    while remaining > 0 {
+        let n = usize::try_from(remaining).unwrap_or(usize::MAX);
+
+        // SAFETY: The file descriptor is valid for the lifetime of `file`, and the offsets are
+        // valid pointers that the kernel updates.
+        let res = unsafe {
+            libc::copy_file_range(file.as_raw_fd(), &mut src, file.as_raw_fd(), &mut dst, n, 0)
+        };
+
+        match res {
+            0 => return Err(ErrorKind::UnexpectedEof.into()),
+            n if n > 0 => remaining -= n as u64,
+            _ => {
+                let e = Error::last_os_error();
+                match e.raw_os_error() {
+                    Some(libc::EINTR) => {}
+                    Some(libc::ENOSYS | libc::EXDEV | libc::EOPNOTSUPP) => {
+                        return Err(ErrorKind::Unsupported.into())
+                    }
+                    _ => return Err(e),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
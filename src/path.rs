@@ -0,0 +1,326 @@
+//! Truncation of files by path, without keeping a handle open.
+
+#[cfg(unix)]
+use std::{
+    convert::TryInto,
+    ffi::{CString, OsStr},
+    fs::File,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::OpenOptionsExt,
+        io::{AsFd, AsRawFd, FromRawFd},
+    },
+};
+use std::{
+    fs::OpenOptions,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+#[cfg(all(unix, not(all(target_os = "linux", not(target_env = "musl")))))]
+use libc::truncate;
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+use libc::truncate64 as truncate;
+
+/// Options for truncating files by path, similar to [`OpenOptions`].
+///
+/// By default symbolic links are followed and missing files are an error of kind
+/// [`NotFound`](ErrorKind::NotFound). Like [`File::set_len`](std::fs::File::set_len), truncating
+/// to a length larger than the current length extends the file with zeros.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::TruncateOptions;
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("file");
+///
+/// TruncateOptions::new()
+///     .create(true)
+///     .follow_symlinks(false)
+///     .truncate(&path, 4)
+///     .unwrap();
+/// assert_eq!(std::fs::read(&path).unwrap(), &[0; 4]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TruncateOptions {
+    follow_symlinks: bool,
+    create: bool,
+}
+
+impl TruncateOptions {
+    /// Creates the default options, following symbolic links and not creating missing files.
+    pub fn new() -> Self {
+        TruncateOptions {
+            follow_symlinks: true,
+            create: false,
+        }
+    }
+
+    /// Sets whether a symbolic link as the last component of the path is followed.
+    ///
+    /// If disabled, truncating a symbolic link is an error instead of truncating its target. This
+    /// only applies to the last component, symbolic links to parent directories are still
+    /// followed. On Unix platforms this uses `O_NOFOLLOW` and fails with `ELOOP`, elsewhere the
+    /// path is checked before opening it, which is not atomic.
+    pub fn follow_symlinks(&mut self, follow_symlinks: bool) -> &mut Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets whether a missing file is created, with the given length.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Truncates the file at `path` to the given new length.
+    ///
+    /// With the default options on Unix platforms this uses `truncate(2)`, otherwise the file is
+    /// opened for writing and closed again.
+    pub fn truncate<P>(&self, path: P, new_len: u64) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        #[cfg(unix)]
+        {
+            if self.follow_symlinks && !self.create {
+                return truncate_unix(path, new_len);
+            }
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(self.create).truncate(false);
+
+        #[cfg(unix)]
+        {
+            if !self.follow_symlinks {
+                options.custom_flags(libc::O_NOFOLLOW);
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            if !self.follow_symlinks {
+                match path.symlink_metadata() {
+                    Ok(m) if m.file_type().is_symlink() => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "path is a symbolic link",
+                        ));
+                    }
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+
+        options.open(path)?.set_len(new_len)
+    }
+
+    /// Truncates the file `name` in the directory `dir` to the given new length.
+    ///
+    /// `name` is resolved relative to `dir` using `openat(2)`, so the directory can't be replaced
+    /// while resolving it. If `name` is absolute, `dir` is ignored.
+    #[cfg(unix)]
+    pub fn truncate_at<D, P>(&self, dir: D, name: P, new_len: u64) -> Result<(), Error>
+    where
+        D: AsFd,
+        P: AsRef<Path>,
+    {
+        let name = c_path(name.as_ref().as_os_str())?;
+
+        let mut flags = libc::O_WRONLY | libc::O_CLOEXEC;
+        if !self.follow_symlinks {
+            flags |= libc::O_NOFOLLOW;
+        }
+        if self.create {
+            flags |= libc::O_CREAT;
+        }
+
+        let fd = loop {
+            // SAFETY: The directory file descriptor is valid for the lifetime of `dir` and `name`
+            // is a NUL-terminated string.
+            let fd = unsafe {
+                libc::openat(
+                    dir.as_fd().as_raw_fd(),
+                    name.as_ptr(),
+                    flags,
+                    0o666 as libc::c_uint,
+                )
+            };
+            if fd >= 0 {
+                break fd;
+            }
+
+            let e = Error::last_os_error();
+            if e.kind() != ErrorKind::Interrupted {
+                return Err(e);
+            }
+        };
+
+        // SAFETY: `openat` returned a new file descriptor that nothing else owns.
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len(new_len)
+    }
+}
+
+impl Default for TruncateOptions {
+    fn default() -> Self {
+        TruncateOptions::new()
+    }
+}
+
+/// Truncate the file at `path` to the given new length, without opening it.
+///
+/// This uses `truncate(2)` on Unix platforms, which follows symbolic links and fails if the file
+/// doesn't exist. See [`TruncateOptions`] to change this.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::truncate_path;
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("file");
+/// std::fs::write(&path, b"hello").unwrap();
+///
+/// truncate_path(&path, 2).unwrap();
+/// assert_eq!(std::fs::read(&path).unwrap(), b"he");
+/// ```
+pub fn truncate_path<P>(path: P, new_len: u64) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    TruncateOptions::new().truncate(path, new_len)
+}
+
+/// Truncate the file `name` in the directory `dir` to the given new length.
+///
+/// Unlike [`truncate_path`], symbolic links are not followed, truncating one is an error. See
+/// [`TruncateOptions::truncate_at`] for details.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::truncate_at;
+/// let dir = tempfile::tempdir().unwrap();
+/// std::fs::write(dir.path().join("file"), b"hello").unwrap();
+///
+/// let handle = std::fs::File::open(dir.path()).unwrap();
+/// truncate_at(&handle, "file", 2).unwrap();
+/// assert_eq!(std::fs::read(dir.path().join("file")).unwrap(), b"he");
+/// ```
+#[cfg(unix)]
+pub fn truncate_at<D, P>(dir: D, name: P, new_len: u64) -> Result<(), Error>
+where
+    D: AsFd,
+    P: AsRef<Path>,
+{
+    TruncateOptions::new()
+        .follow_symlinks(false)
+        .truncate_at(dir, name, new_len)
+}
+
+#[cfg(unix)]
+fn c_path(path: &OsStr) -> Result<CString, Error> {
+    CString::new(path.as_bytes())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+#[cfg(unix)]
+fn truncate_unix(path: &Path, new_len: u64) -> Result<(), Error> {
+    let path = c_path(path.as_os_str())?;
+    let new_len = new_len.try_into().map_err(|_| ErrorKind::InvalidInput)?;
+
+    loop {
+        // SAFETY: `path` is a NUL-terminated string.
+        if unsafe { truncate(path.as_ptr(), new_len) } == 0 {
+            return Ok(());
+        }
+
+        let e = Error::last_os_error();
+        if e.kind() != ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate_path, TruncateOptions};
+    use std::{fs, io::ErrorKind};
+
+    #[test]
+    fn path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+
+        let e = truncate_path(&path, 0).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+
+        fs::write(&path, b"0123").unwrap();
+        truncate_path(&path, 2).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"01");
+        truncate_path(&path, 3).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"01\0");
+    }
+
+    #[test]
+    fn create() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+
+        TruncateOptions::new()
+            .create(true)
+            .truncate(&path, 2)
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), &[0, 0]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        let link = dir.path().join("link");
+        fs::write(&path, b"0123").unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        truncate_path(&link, 3).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"012");
+
+        assert!(TruncateOptions::new()
+            .follow_symlinks(false)
+            .truncate(&link, 2)
+            .is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"012");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn at() {
+        use super::truncate_at;
+
+        let dir = tempfile::tempdir().unwrap();
+        let handle = fs::File::open(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), b"0123").unwrap();
+        std::os::unix::fs::symlink("file", dir.path().join("link")).unwrap();
+
+        truncate_at(&handle, "file", 1).unwrap();
+        assert_eq!(fs::read(dir.path().join("file")).unwrap(), b"0");
+
+        assert!(truncate_at(&handle, "link", 0).is_err());
+        let e = truncate_at(&handle, "missing", 0).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+        let e = truncate_at(&handle, "a\0b", 0).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        TruncateOptions::new()
+            .create(true)
+            .truncate_at(&handle, "new", 2)
+            .unwrap();
+        assert_eq!(fs::read(dir.path().join("new")).unwrap(), &[0, 0]);
+    }
+}
@@ -0,0 +1,695 @@
+//! Recovery of append logs that end with a torn record.
+
+use crate::Truncate;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Seek, SeekFrom};
+
+/// A format of records stored back to back, used by [`recover_tail`].
+pub trait RecordFormat {
+    /// Reads a single record from the start of `reader`.
+    ///
+    /// Returns the length of the record in bytes if it is complete and valid. Returns `None` if
+    /// the data is incomplete or invalid, for example because the record was only partially
+    /// written. `reader` is never empty when this is called.
+    ///
+    /// Errors are only returned for failures of the underlying reader, which abort the recovery.
+    fn read_record(&self, reader: &mut dyn BufRead) -> Result<Option<u64>, Error>;
+}
+
+impl<F> RecordFormat for &F
+where
+    F: RecordFormat + ?Sized,
+{
+    fn read_record(&self, reader: &mut dyn BufRead) -> Result<Option<u64>, Error> {
+        (**self).read_record(reader)
+    }
+}
+
+/// Truncate an append log after the last complete record.
+///
+/// The object is read from the start, record by record, using the given [`RecordFormat`]. At the
+/// first record that is incomplete or invalid, the object is truncated, discarding it and all data
+/// after it. Afterwards the object is positioned at its new end, ready for appending.
+///
+/// Returns the new length of the object. The truncation is done with a single call to
+/// [`Truncate::truncate_u64`], even if the object already ends with a complete record.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{recover_tail, Lines};
+/// # use std::io::Cursor;
+/// let mut log = Cursor::new(b"first\nsecond\nthi".to_vec());
+///
+/// assert_eq!(recover_tail(&mut log, &Lines).unwrap(), 13);
+/// assert_eq!(log.get_ref(), b"first\nsecond\n");
+/// ```
+pub fn recover_tail<T, F>(io: &mut T, format: &F) -> Result<u64, Error>
+where
+    T: Read + Seek + Truncate + ?Sized,
+    F: RecordFormat + ?Sized,
+    Error: From<T::Error>,
+{
+    io.seek(SeekFrom::Start(0))?;
+
+    let mut valid = 0;
+    let mut reader = BufReader::new(&mut *io);
+    while !reader.fill_buf()?.is_empty() {
+        match format.read_record(&mut reader)? {
+            Some(n) => valid += n,
+            None => break,
+        }
+    }
+    drop(reader);
+
+    io.truncate_u64(valid)?;
+    io.seek(SeekFrom::Start(valid))?;
+    Ok(valid)
+}
+
+/// Maps unexpected ends of input to `None`, since they indicate an incomplete record.
+fn incomplete<T>(res: Result<T, Error>) -> Result<Option<T>, Error> {
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Byte order of the length prefix and checksum of [`LengthPrefixed`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+/// Size of the length prefix of [`LengthPrefixed`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixWidth {
+    /// A 4 byte prefix.
+    U32,
+    /// An 8 byte prefix.
+    U64,
+}
+
+/// Records consisting of a length prefix followed by the payload, and optionally a CRC-32
+/// checksum of the payload.
+///
+/// The length prefix counts only the payload, not itself or the checksum. The checksum is the
+/// common CRC-32 (ISO-HDLC, as used by zlib and Ethernet) and uses the same byte order as the
+/// prefix.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{recover_tail, Endian, LengthPrefixed, PrefixWidth};
+/// # use std::io::Cursor;
+/// let format = LengthPrefixed {
+///     width: PrefixWidth::U32,
+///     endian: Endian::Big,
+///     crc32: false,
+/// };
+///
+/// let mut log = Cursor::new(vec![0, 0, 0, 2, b'h', b'i', 0, 0, 0, 5, b'w']);
+/// assert_eq!(recover_tail(&mut log, &format).unwrap(), 6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LengthPrefixed {
+    /// The size of the length prefix.
+    pub width: PrefixWidth,
+    /// The byte order of the length prefix and checksum.
+    pub endian: Endian,
+    /// Whether each record ends with a CRC-32 checksum of its payload.
+    pub crc32: bool,
+}
+
+impl LengthPrefixed {
+    fn read_prefix(&self, reader: &mut dyn BufRead) -> Result<u64, Error> {
+        let mut buf = [0; 8];
+        let (buf, width) = match self.width {
+            PrefixWidth::U32 => (&mut buf[4..], 4),
+            PrefixWidth::U64 => (&mut buf[..], 8),
+        };
+        reader.read_exact(buf)?;
+
+        let mut value = 0;
+        for i in 0..width {
+            let byte = match self.endian {
+                Endian::Little => buf[width - 1 - i],
+                Endian::Big => buf[i],
+            };
+            value = value << 8 | u64::from(byte);
+        }
+        Ok(value)
+    }
+
+    fn read_crc(&self, reader: &mut dyn BufRead) -> Result<u32, Error> {
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf)?;
+        Ok(match self.endian {
+            Endian::Little => u32::from_le_bytes(buf),
+            Endian::Big => u32::from_be_bytes(buf),
+        })
+    }
+}
+
+impl RecordFormat for LengthPrefixed {
+    fn read_record(&self, reader: &mut dyn BufRead) -> Result<Option<u64>, Error> {
+        let len = match incomplete(self.read_prefix(reader))? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        // Consume the payload in chunks, a torn prefix may claim an arbitrarily large length
+        let mut crc = Crc32::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            let n = if remaining < buf.len() as u64 {
+                remaining as usize
+            } else {
+                buf.len()
+            };
+            if self.crc32 {
+                crc.update(&buf[..n]);
+            }
+            reader.consume(n);
+            remaining -= n as u64;
+        }
+
+        let prefix_len = match self.width {
+            PrefixWidth::U32 => 4,
+            PrefixWidth::U64 => 8,
+        };
+
+        if !self.crc32 {
+            return Ok(Some(prefix_len + len));
+        }
+
+        match incomplete(self.read_crc(reader))? {
+            Some(expected) if expected == crc.finish() => Ok(Some(prefix_len + len + 4)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Newline-delimited records. Each record ends with a `\n` byte.
+///
+/// The contents of the lines are not validated, only a missing final newline is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lines;
+
+impl RecordFormat for Lines {
+    fn read_record(&self, reader: &mut dyn BufRead) -> Result<Option<u64>, Error> {
+        let mut len = 0;
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            match buf.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    reader.consume(i + 1);
+                    return Ok(Some(len + i as u64 + 1));
+                }
+                None => {
+                    let n = buf.len();
+                    reader.consume(n);
+                    len += n as u64;
+                }
+            }
+        }
+    }
+}
+
+/// [JSON Lines](https://jsonlines.org/) records. Each record is a valid JSON value on a single
+/// line, ending with a `\n` byte.
+///
+/// Unlike [`Lines`], a line that is not valid JSON is also treated as invalid, which detects
+/// records that were torn in the middle but happen to be followed by a newline. Each line is
+/// buffered for validation, so lines longer than [`max_line_len`](JsonLines::max_line_len) are
+/// treated as invalid as well.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{recover_tail, JsonLines};
+/// # use std::io::Cursor;
+/// let format = JsonLines { max_line_len: 16 };
+///
+/// let mut log = Cursor::new(b"[1, 2]\n\"too long for the limit\"\n".to_vec());
+/// assert_eq!(recover_tail(&mut log, &format).unwrap(), 7);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JsonLines {
+    /// The maximum length of a line in bytes, including the newline. Defaults to
+    /// [`DEFAULT_MAX_LINE_LEN`](JsonLines::DEFAULT_MAX_LINE_LEN).
+    pub max_line_len: u64,
+}
+
+impl JsonLines {
+    /// The default maximum line length of 64 MiB.
+    pub const DEFAULT_MAX_LINE_LEN: u64 = 64 * 1024 * 1024;
+}
+
+impl Default for JsonLines {
+    fn default() -> Self {
+        JsonLines {
+            max_line_len: Self::DEFAULT_MAX_LINE_LEN,
+        }
+    }
+}
+
+impl RecordFormat for JsonLines {
+    fn read_record(&self, reader: &mut dyn BufRead) -> Result<Option<u64>, Error> {
+        // A torn final record may be arbitrarily long, don't buffer more than the limit
+        let mut line = Vec::new();
+        reader
+            .take(self.max_line_len)
+            .read_until(b'\n', &mut line)?;
+
+        if line.last() != Some(&b'\n') {
+            return Ok(None);
+        }
+
+        let valid = std::str::from_utf8(&line).is_ok() && json::is_value(&line[..line.len() - 1]);
+        Ok(if valid { Some(line.len() as u64) } else { None })
+    }
+}
+
+/// Incremental CRC-32 (ISO-HDLC) computation.
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    pub(crate) fn new() -> Self {
+        Crc32(!0)
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 = Self::TABLE[((self.0 ^ u32::from(*byte)) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// Minimal JSON syntax validation, without building any values.
+mod json {
+    /// Maximum nesting depth of arrays and objects, deeper values are treated as invalid.
+    const MAX_DEPTH: usize = 256;
+
+    /// Returns `true` if `input` is a single JSON value, optionally surrounded by whitespace.
+    pub(super) fn is_value(input: &[u8]) -> bool {
+        let mut parser = Parser { input, pos: 0 };
+        parser.value(0) && {
+            parser.whitespace();
+            parser.pos == input.len()
+        }
+    }
+
+    struct Parser<'a> {
+        input: &'a [u8],
+        pos: usize,
+    }
+
+    impl Parser<'_> {
+        fn peek(&self) -> Option<u8> {
+            self.input.get(self.pos).copied()
+        }
+
+        fn eat(&mut self, byte: u8) -> bool {
+            if self.peek() == Some(byte) {
+                self.pos += 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn whitespace(&mut self) {
+            while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+                self.pos += 1;
+            }
+        }
+
+        fn value(&mut self, depth: usize) -> bool {
+            self.whitespace();
+            match self.peek() {
+                Some(b'{') if depth < MAX_DEPTH => self.object(depth + 1),
+                Some(b'[') if depth < MAX_DEPTH => self.array(depth + 1),
+                Some(b'"') => self.string(),
+                Some(b'-' | b'0'..=b'9') => self.number(),
+                Some(b't') => self.literal(b"true"),
+                Some(b'f') => self.literal(b"false"),
+                Some(b'n') => self.literal(b"null"),
+                _ => false,
+            }
+        }
+
+        fn object(&mut self, depth: usize) -> bool {
+            self.pos += 1;
+            self.whitespace();
+            if self.eat(b'}') {
+                return true;
+            }
+
+            loop {
+                self.whitespace();
+                if !self.string() {
+                    return false;
+                }
+                self.whitespace();
+                if !self.eat(b':') || !self.value(depth) {
+                    return false;
+                }
+                self.whitespace();
+                if self.eat(b'}') {
+                    return true;
+                }
+                if !self.eat(b',') {
+                    return false;
+                }
+            }
+        }
+
+        fn array(&mut self, depth: usize) -> bool {
+            self.pos += 1;
+            self.whitespace();
+            if self.eat(b']') {
+                return true;
+            }
+
+            loop {
+                if !self.value(depth) {
+                    return false;
+                }
+                self.whitespace();
+                if self.eat(b']') {
+                    return true;
+                }
+                if !self.eat(b',') {
+                    return false;
+                }
+            }
+        }
+
+        fn string(&mut self) -> bool {
+            if !self.eat(b'"') {
+                return false;
+            }
+
+            loop {
+                match self.peek() {
+                    Some(b'"') => {
+                        self.pos += 1;
+                        return true;
+                    }
+                    Some(b'\\') => {
+                        self.pos += 1;
+                        match self.peek() {
+                            Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {
+                                self.pos += 1
+                            }
+                            Some(b'u') => {
+                                self.pos += 1;
+                                for _ in 0..4 {
+                                    if !self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+                                        return false;
+                                    }
+                                    self.pos += 1;
+                                }
+                            }
+                            _ => return false,
+                        }
+                    }
+                    Some(0x00..=0x1f) | None => return false,
+                    Some(_) => self.pos += 1,
+                }
+            }
+        }
+
+        fn digits(&mut self) -> bool {
+            let start = self.pos;
+            while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                self.pos += 1;
+            }
+            self.pos > start
+        }
+
+        fn number(&mut self) -> bool {
+            self.eat(b'-');
+            if !self.eat(b'0') && !self.digits() {
+                return false;
+            }
+            if self.eat(b'.') && !self.digits() {
+                return false;
+            }
+            if self.eat(b'e') || self.eat(b'E') {
+                if !self.eat(b'+') {
+                    self.eat(b'-');
+                }
+                if !self.digits() {
+                    return false;
+                }
+            }
+            true
+        }
+
+        fn literal(&mut self, literal: &[u8]) -> bool {
+            if self.input[self.pos..].starts_with(literal) {
+                self.pos += literal.len();
+                true
+            } else {
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        json, recover_tail, Crc32, Endian, JsonLines, LengthPrefixed, Lines, PrefixWidth,
+        RecordFormat,
+    };
+    use std::io::{self, BufRead, Cursor, Seek, SeekFrom, Write};
+
+    fn rest(reader: &mut dyn BufRead) -> Vec<u8> {
+        let mut buf = Vec::new();
+        io::copy(reader, &mut buf).unwrap();
+        buf
+    }
+
+    fn frame(format: &LengthPrefixed, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len() as u64;
+        let mut buf = match (format.width, format.endian) {
+            (PrefixWidth::U32, Endian::Little) => (len as u32).to_le_bytes().to_vec(),
+            (PrefixWidth::U32, Endian::Big) => (len as u32).to_be_bytes().to_vec(),
+            (PrefixWidth::U64, Endian::Little) => len.to_le_bytes().to_vec(),
+            (PrefixWidth::U64, Endian::Big) => len.to_be_bytes().to_vec(),
+        };
+        buf.extend_from_slice(payload);
+
+        if format.crc32 {
+            let mut crc = Crc32::new();
+            crc.update(payload);
+            match format.endian {
+                Endian::Little => buf.extend_from_slice(&crc.finish().to_le_bytes()),
+                Endian::Big => buf.extend_from_slice(&crc.finish().to_be_bytes()),
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        for &width in &[PrefixWidth::U32, PrefixWidth::U64] {
+            for &endian in &[Endian::Little, Endian::Big] {
+                for &crc32 in &[false, true] {
+                    let format = LengthPrefixed {
+                        width,
+                        endian,
+                        crc32,
+                    };
+
+                    let mut data = frame(&format, b"first");
+                    data.extend(frame(&format, b""));
+                    let valid = data.len();
+                    let last = frame(&format, b"last record");
+
+                    // Every possible torn position of the last record
+                    for torn in 0..last.len() {
+                        let mut log = Cursor::new(data.clone());
+                        log.get_mut().extend_from_slice(&last[..torn]);
+
+                        assert_eq!(recover_tail(&mut log, &format).unwrap(), valid as u64);
+                        assert_eq!(log.get_ref(), &data);
+                        assert_eq!(log.position(), valid as u64);
+                    }
+
+                    let mut log = Cursor::new(data.clone());
+                    log.get_mut().extend_from_slice(&last);
+                    let len = log.get_ref().len() as u64;
+                    assert_eq!(recover_tail(&mut log, &format).unwrap(), len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let format = LengthPrefixed {
+            width: PrefixWidth::U32,
+            endian: Endian::Little,
+            crc32: true,
+        };
+
+        let mut data = frame(&format, b"first");
+        let valid = data.len() as u64;
+        let mut corrupt = frame(&format, b"second");
+        corrupt[5] ^= 0xff;
+        data.extend(corrupt);
+        data.extend(frame(&format, b"third"));
+
+        let mut log = Cursor::new(data);
+        assert_eq!(recover_tail(&mut log, &format).unwrap(), valid);
+    }
+
+    #[test]
+    fn huge_prefix() {
+        let format = LengthPrefixed {
+            width: PrefixWidth::U64,
+            endian: Endian::Little,
+            crc32: false,
+        };
+
+        let mut log = Cursor::new(u64::MAX.to_le_bytes().to_vec());
+        assert_eq!(recover_tail(&mut log, &format).unwrap(), 0);
+    }
+
+    #[test]
+    fn lines() {
+        let mut log = Cursor::new(b"a\n\nbc\nd".to_vec());
+        assert_eq!(recover_tail(&mut log, &Lines).unwrap(), 6);
+        assert_eq!(log.get_ref(), b"a\n\nbc\n");
+
+        // Consumes exactly one line
+        let mut reader: &[u8] = b"ab\ncd\n";
+        assert_eq!(Lines.read_record(&mut reader).unwrap(), Some(3));
+        assert_eq!(rest(&mut reader), b"cd\n");
+    }
+
+    #[test]
+    fn json_lines() {
+        let data = b"{\"a\": [1, -2.5e3, true, null]}\n\"x\\u00e9\"\r\n{\"torn\": \n{}\n";
+        let mut log = Cursor::new(data.to_vec());
+        assert_eq!(recover_tail(&mut log, &JsonLines::default()).unwrap(), 42);
+        assert_eq!(log.get_ref(), &data[..42]);
+    }
+
+    #[test]
+    fn json_lines_long() {
+        // A string filling the maximum line length, then one that is a byte longer
+        let line = |len: u64| {
+            let mut line = vec![b'a'; len as usize];
+            line[0] = b'"';
+            line[len as usize - 2] = b'"';
+            line[len as usize - 1] = b'\n';
+            line
+        };
+        let format = JsonLines { max_line_len: 100 };
+        let mut data = line(100);
+        data.extend(line(101));
+
+        let mut log = Cursor::new(data);
+        assert_eq!(recover_tail(&mut log, &format).unwrap(), 100);
+        assert_eq!(log.get_ref().len(), 100);
+    }
+
+    #[test]
+    fn json() {
+        for valid in &[
+            &b"null"[..],
+            b" 0 ",
+            b"-0.5E+10",
+            b"[]",
+            b"[1, [2, {}]]",
+            b"{\"a\": {\"b\": \"\\\"\"}}",
+        ] {
+            assert!(
+                json::is_value(valid),
+                "{:?}",
+                String::from_utf8_lossy(valid)
+            );
+        }
+
+        for invalid in &[
+            &b""[..],
+            b"01",
+            b"1.",
+            b"[1,]",
+            b"{\"a\" 1}",
+            b"{1: 2}",
+            b"\"\\x\"",
+            b"\"a",
+            b"nul",
+            b"1 2",
+            b"\"\t\"",
+        ] {
+            assert!(
+                !json::is_value(invalid),
+                "{:?}",
+                String::from_utf8_lossy(invalid)
+            );
+        }
+
+        assert!(!json::is_value(&[b'['; 1000]));
+    }
+
+    #[test]
+    fn file() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(b"one\ntwo\nthr").unwrap();
+        f.seek(SeekFrom::Start(2)).unwrap();
+
+        assert_eq!(recover_tail(&mut f, &Lines).unwrap(), 8);
+        assert_eq!(f.metadata().unwrap().len(), 8);
+        assert_eq!(f.stream_position().unwrap(), 8);
+    }
+}
@@ -0,0 +1,232 @@
+//! Removal of a range of bytes from the middle of an object.
+
+use crate::copy::{check_not_append, copy_down, try_collapse};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Cursor, Error, ErrorKind, Seek, SeekFrom},
+    ops::Range,
+};
+
+/// A trait for IO objects that can be shortened by removing a range of bytes anywhere in them.
+///
+/// The data after the range moves up to the start of the range. This generalizes
+/// [`Truncate`](crate::Truncate), which removes the range from `new_len` to the end, and
+/// [`TruncateFront`](crate::TruncateFront), which removes a range at the start.
+pub trait RemoveRange {
+    /// Remove the bytes in `range`, shortening the object by its length.
+    ///
+    /// All implementations in this crate return an error of kind
+    /// [`InvalidInput`](ErrorKind::InvalidInput) if the range ends after the end of the object or
+    /// starts after its end. Removing an empty range does nothing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::RemoveRange;
+    /// let mut v = b"header[metadata]body".to_vec();
+    /// v.remove_range(6..16).unwrap();
+    /// assert_eq!(v, b"headerbody");
+    /// ```
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error>;
+}
+
+/// Checks that `range` lies in an object of length `len`.
+fn check_range(range: &Range<u64>, len: u64) -> Result<(), Error> {
+    if range.start > range.end || range.end > len {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("range {:?} is out of bounds for length {}", range, len),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the position after removing `range`, so that it keeps pointing at the same data, or
+/// at the start of the range if it was in it.
+fn adjust_position(position: u64, range: &Range<u64>) -> u64 {
+    if position >= range.end {
+        position - (range.end - range.start)
+    } else {
+        position.min(range.start)
+    }
+}
+
+impl RemoveRange for File {
+    /// On Linux, the range is collapsed using `FALLOC_FL_COLLAPSE_RANGE` if the filesystem
+    /// supports it, both ends of the range are multiples of the block size and the range ends
+    /// before the end of the file. Otherwise the data after the range is copied down onto it,
+    /// followed by a call to [`File::set_len`]. If the copy is interrupted, the part of the tail
+    /// that was already copied appears twice until the operation is repeated.
+    ///
+    /// The file position is adjusted like for [`Cursor`].
+    ///
+    /// Fails with an error of kind [`InvalidInput`](ErrorKind::InvalidInput) for files opened in
+    /// append mode on Unix, where the copied data would be appended instead of overwriting the
+    /// range.
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        let len = self.metadata()?.len();
+        check_range(&range, len)?;
+
+        let removed = range.end - range.start;
+        if removed == 0 {
+            return Ok(());
+        }
+        check_not_append(self)?;
+
+        let mut file: &File = self;
+        let position = file.stream_position()?;
+
+        if !try_collapse(file, range.start, removed)? {
+            copy_down(&mut file, range.end, range.start, len - range.end)?;
+            file.set_len(len - removed)?;
+        }
+
+        file.seek(SeekFrom::Start(adjust_position(position, &range)))?;
+        Ok(())
+    }
+}
+
+impl RemoveRange for Vec<u8> {
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        check_range(&range, self.len() as u64)?;
+        self.drain(range.start as usize..range.end as usize);
+        Ok(())
+    }
+}
+
+impl RemoveRange for VecDeque<u8> {
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        check_range(&range, self.len() as u64)?;
+        self.drain(range.start as usize..range.end as usize);
+        Ok(())
+    }
+}
+
+impl<T> RemoveRange for Cursor<T>
+where
+    T: RemoveRange,
+{
+    /// Delegates to the contained [`RemoveRange`] impl. If the cursor lies after the range, it is
+    /// moved back by the length of the range so that it keeps pointing at the same data. If it
+    /// lies in the range, it is moved to the start of the range.
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        self.get_mut().remove_range(range.clone())?;
+        self.set_position(adjust_position(self.position(), &range));
+        Ok(())
+    }
+}
+
+impl<T> RemoveRange for &mut T
+where
+    T: RemoveRange,
+{
+    fn remove_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        (**self).remove_range(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoveRange;
+    use crate::copy::fixtures;
+    use std::{
+        collections::VecDeque,
+        fs,
+        io::{Cursor, ErrorKind, Seek, SeekFrom, Write},
+    };
+
+    #[test]
+    fn vec() {
+        let mut v: Vec<u8> = vec![0, 1, 2, 3, 4];
+
+        v.remove_range(1..3).unwrap();
+        assert_eq!(v, &[0, 3, 4]);
+        v.remove_range(3..3).unwrap();
+        v.remove_range(2..3).unwrap();
+        assert_eq!(v, &[0, 3]);
+
+        // Error
+        let e = v.remove_range(1..3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        #[allow(clippy::reversed_empty_ranges)]
+        let e = v.remove_range(2..1).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(v, &[0, 3]);
+    }
+
+    #[test]
+    fn vec_deque() {
+        let mut v: VecDeque<u8> = vec![0, 1, 2, 3, 4].into();
+
+        v.remove_range(0..2).unwrap();
+        assert_eq!(v, &[2, 3, 4]);
+
+        // Error
+        let e = v.remove_range(0..4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut v = Cursor::new(vec![0, 1, 2, 3, 4, 5]);
+
+        // After the range
+        v.set_position(5);
+        v.remove_range(1..3).unwrap();
+        assert_eq!(v.get_ref(), &[0, 3, 4, 5]);
+        assert_eq!(v.position(), 3);
+
+        // In the range
+        v.remove_range(2..4).unwrap();
+        assert_eq!(v.get_ref(), &[0, 3]);
+        assert_eq!(v.position(), 2);
+
+        // Before the range
+        v.set_position(0);
+        v.remove_range(1..2).unwrap();
+        assert_eq!(v.position(), 0);
+    }
+
+    #[test]
+    fn file() {
+        fixtures::files(|mut f| {
+            let block = 4096;
+            let mut expected = fixtures::data(block * 5);
+            f.write_all(&expected).unwrap();
+
+            // Not block aligned, always uses the fallback
+            f.remove_range(1..block + 2).unwrap();
+            expected.drain(1..block as usize + 2);
+            assert_eq!(f.stream_position().unwrap(), expected.len() as u64);
+
+            // Block aligned, collapsed if the filesystem supports it
+            f.seek(SeekFrom::Start(2 * block + 1)).unwrap();
+            f.remove_range(block..2 * block).unwrap();
+            expected.drain(block as usize..2 * block as usize);
+            assert_eq!(f.stream_position().unwrap(), block + 1);
+
+            // At the end
+            let len = expected.len() as u64;
+            f.remove_range(len - 10..len).unwrap();
+            expected.truncate(expected.len() - 10);
+            assert_eq!(f.stream_position().unwrap(), block + 1);
+
+            assert!(fixtures::read_all(&f) == expected);
+
+            // Error
+            let e = f.remove_range(0..len).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_append() {
+        let (named, mut f) = fixtures::append_file();
+
+        let e = f.remove_range(2..4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(fs::read(named.path()).unwrap(), b"0123456789");
+    }
+}
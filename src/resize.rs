@@ -0,0 +1,231 @@
+//! Resizing with an explicit policy for growing.
+
+use crate::TruncateError;
+use std::{
+    cmp,
+    collections::VecDeque,
+    fs::File,
+    io::{Cursor, Error, Seek, SeekFrom, Write},
+};
+
+/// What to do when an object is resized to a length larger than its current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GrowPolicy {
+    /// Return an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput), wrapping a
+    /// [`TruncateError::GrowNotSupported`].
+    Error,
+    /// Extend the object with zero bytes that are actually written out.
+    ZeroFill,
+    /// Extend the object with the given byte.
+    Fill(u8),
+    /// Extend the object with zero bytes without allocating them, if the object supports that.
+    ///
+    /// For files this creates a hole on filesystems that support sparse files. In-memory buffers
+    /// treat this the same as [`ZeroFill`](GrowPolicy::ZeroFill).
+    Sparse,
+}
+
+/// A trait for IO objects that can be shortened as well as extended.
+///
+/// Unlike [`Truncate::truncate`](crate::Truncate::truncate), the behavior when growing is chosen
+/// by the caller through a [`GrowPolicy`] and is the same for every implementation.
+pub trait Resize {
+    /// Resize the object to the given new length in bytes.
+    ///
+    /// If `new_len` is smaller than the current length, the object is truncated. If it is larger,
+    /// the object is extended according to `policy`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::{GrowPolicy, Resize};
+    /// # use std::io::Cursor;
+    /// let mut v = Cursor::new(vec![0, 1, 2, 3]);
+    /// v.resize(6, GrowPolicy::Fill(0xff)).unwrap();
+    /// assert_eq!(v.get_ref(), &[0, 1, 2, 3, 0xff, 0xff]);
+    ///
+    /// v.resize(2, GrowPolicy::Error).unwrap();
+    /// assert_eq!(v.get_ref(), &[0, 1]);
+    /// ```
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error>;
+}
+
+fn grow_error(new_len: usize, len: u64) -> Error {
+    TruncateError::GrowNotSupported {
+        requested: new_len as u64,
+        current: len,
+    }
+    .into()
+}
+
+/// Writes `byte` into the range `start..end` of the file, restoring the file position afterwards.
+fn fill(mut file: &File, start: u64, end: u64, byte: u8) -> Result<(), Error> {
+    let buf = [byte; 8 * 1024];
+    let position = file.stream_position()?;
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut remaining = end - start;
+    while remaining > 0 {
+        let n = cmp::min(remaining, buf.len() as u64) as usize;
+        file.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+
+    file.seek(SeekFrom::Start(position))?;
+    Ok(())
+}
+
+impl Resize for File {
+    /// Shrinking and [`GrowPolicy::Sparse`] delegate to [`File::set_len`]. The filling policies
+    /// write the new bytes out explicitly. The file position is not changed.
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error> {
+        let len = self.metadata()?.len();
+        let new_len_u64 = new_len as u64;
+
+        if new_len_u64 <= len {
+            return self.set_len(new_len_u64);
+        }
+
+        match policy {
+            GrowPolicy::Error => Err(grow_error(new_len, len)),
+            GrowPolicy::ZeroFill => fill(self, len, new_len_u64, 0),
+            GrowPolicy::Fill(byte) => fill(self, len, new_len_u64, byte),
+            GrowPolicy::Sparse => self.set_len(new_len_u64),
+        }
+    }
+}
+
+impl Resize for Vec<u8> {
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error> {
+        let byte = match policy {
+            GrowPolicy::Error if new_len > self.len() => {
+                return Err(grow_error(new_len, self.len() as u64))
+            }
+            GrowPolicy::Fill(byte) => byte,
+            _ => 0,
+        };
+
+        Vec::resize(self, new_len, byte);
+        Ok(())
+    }
+}
+
+impl Resize for VecDeque<u8> {
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error> {
+        let byte = match policy {
+            GrowPolicy::Error if new_len > self.len() => {
+                return Err(grow_error(new_len, self.len() as u64))
+            }
+            GrowPolicy::Fill(byte) => byte,
+            _ => 0,
+        };
+
+        VecDeque::resize(self, new_len, byte);
+        Ok(())
+    }
+}
+
+impl<T> Resize for Cursor<T>
+where
+    T: Resize,
+{
+    /// Delegates to the contained [`Resize`] impl. The cursor will be moved to the end of the
+    /// data if it lies in the truncated area.
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error> {
+        self.get_mut().resize(new_len, policy)?;
+        self.set_position(cmp::min(new_len as u64, self.position()));
+        Ok(())
+    }
+}
+
+impl<T> Resize for &mut T
+where
+    T: Resize,
+{
+    fn resize(&mut self, new_len: usize, policy: GrowPolicy) -> Result<(), Error> {
+        (**self).resize(new_len, policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrowPolicy, Resize};
+    use std::{
+        collections::VecDeque,
+        io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+    };
+
+    #[test]
+    fn vec() {
+        let mut v: Vec<u8> = vec![0, 1, 2, 3];
+
+        Resize::resize(&mut v, 3, GrowPolicy::Error).unwrap();
+        assert_eq!(v, &[0, 1, 2]);
+
+        Resize::resize(&mut v, 4, GrowPolicy::ZeroFill).unwrap();
+        assert_eq!(v, &[0, 1, 2, 0]);
+
+        Resize::resize(&mut v, 5, GrowPolicy::Fill(7)).unwrap();
+        assert_eq!(v, &[0, 1, 2, 0, 7]);
+
+        // Error
+        let e = Resize::resize(&mut v, 6, GrowPolicy::Error).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn vec_deque() {
+        let mut v: VecDeque<u8> = vec![0, 1, 2, 3].into();
+
+        Resize::resize(&mut v, 2, GrowPolicy::Error).unwrap();
+        assert_eq!(v, &[0, 1]);
+
+        Resize::resize(&mut v, 3, GrowPolicy::Sparse).unwrap();
+        assert_eq!(v, &[0, 1, 0]);
+
+        // Error
+        let e = Resize::resize(&mut v, 4, GrowPolicy::Error).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3]);
+
+        v.set_position(4); // end of data
+        v.resize(2, GrowPolicy::Error).unwrap();
+        assert_eq!(v.get_ref(), &[0, 1]);
+        assert_eq!(v.position(), 2);
+
+        v.resize(3, GrowPolicy::Fill(1)).unwrap();
+        assert_eq!(v.get_ref(), &[0, 1, 1]);
+        assert_eq!(v.position(), 2);
+    }
+
+    #[test]
+    fn file() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&[0, 1, 2, 3]).unwrap();
+        f.seek(SeekFrom::Start(1)).unwrap();
+
+        f.resize(3, GrowPolicy::Error).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 3);
+
+        // Error
+        let e = f.resize(4, GrowPolicy::Error).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        f.resize(5, GrowPolicy::Fill(9)).unwrap();
+        f.resize(6, GrowPolicy::ZeroFill).unwrap();
+        f.resize(7, GrowPolicy::Sparse).unwrap();
+
+        // Position is unchanged
+        assert_eq!(f.stream_position().unwrap(), 1);
+
+        let mut buf = Vec::new();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, &[0, 1, 2, 9, 9, 0, 0]);
+    }
+}
@@ -0,0 +1,990 @@
+//! Keeping only a range of an object, with recovery of interrupted operations.
+//!
+//! The retained data is moved to the start of the object in place. Before anything is moved, a
+//! trailer describing the operation is appended to the object, and the progress of the move is
+//! recorded regularly. The final truncation removes the trailer together with the data after the
+//! range, so an object that still ends in a valid trailer was interrupted and can be resumed.
+//!
+//! The data is copied front to back, so the copy only overwrites data that was already copied,
+//! or that is being copied by the same step. If the data is moved by at least 1 MiB, the
+//! source and destination of a step never overlap and repeating the steps after the recorded
+//! progress is safe, as long as the progress is recorded at least every `start` bytes. Moving
+//! by less than that is done in steps that are first written to a journal before the trailer,
+//! so an interrupted step can be repeated from the journal.
+
+use crate::{
+    copy::{check_not_append, try_collapse},
+    recover::Crc32,
+    Truncate, TruncateError,
+};
+use std::{
+    cmp,
+    convert::TryInto,
+    fs::File,
+    io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+const MAGIC: [u8; 8] = *b"IOTRUNC\x01";
+const TRAILER_LEN: u64 = 64;
+const SLOT_OFFSETS: [usize; 2] = [40, 52];
+const JOURNAL_HEADER_LEN: u64 = 16;
+
+/// The maximum length copied at once.
+const MAX_CHUNK: u64 = 1024 * 1024;
+/// The maximum length copied between recording the progress.
+const MAX_CHECKPOINT: u64 = 64 * 1024 * 1024;
+
+/// A trait for keeping only a part of an object, discarding the data before and after it.
+///
+/// The operations are restart-safe: If one is interrupted, for example by a crash or an IO
+/// error, [`pending_retain`](Retain::pending_retain) detects this and
+/// [`resume_retain`](Retain::resume_retain) completes it. Until then, the object ends in a
+/// trailer of 64 bytes, and the other operations fail with an error of kind
+/// [`Other`](ErrorKind::Other).
+///
+/// The default implementations work with any object by copying the data through a buffer, and
+/// only [`flush`](Write::flush) the object, so they can resume operations interrupted by an
+/// error but not necessarily by a crash. The impls for [`File`] sync the file as required and
+/// move the data in the kernel where possible.
+pub trait Retain: Read + Write + Seek + Truncate {
+    /// Keep only the bytes in `range`, moving them to the start of the object.
+    ///
+    /// Fails with an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the range ends
+    /// after the end of the object. The position is moved back by the number of removed bytes, or
+    /// to the start if it lies in the removed area.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Retain;
+    /// # use std::io::Cursor;
+    /// let mut c = Cursor::new(b"header|body|footer".to_vec());
+    /// c.retain_range(7..11).unwrap();
+    /// assert_eq!(c.get_ref(), b"body");
+    /// ```
+    fn retain_range(&mut self, range: Range<u64>) -> Result<(), Error>
+    where
+        Error: From<Self::Error>,
+    {
+        retain(&mut Portable::new(self), range)
+    }
+
+    /// Keep only the last `len` bytes of the object, or all of them if it is shorter.
+    ///
+    /// This is [`retain_range`](Retain::retain_range) with the range ending at the current end.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Retain;
+    /// # use std::io::Write;
+    /// let mut file = tempfile::tempfile().unwrap();
+    /// file.write_all(b"old lines\nnew lines\n").unwrap();
+    ///
+    /// file.retain_tail(10).unwrap();
+    /// assert_eq!(file.metadata().unwrap().len(), 10);
+    /// ```
+    fn retain_tail(&mut self, len: u64) -> Result<(), Error>
+    where
+        Error: From<Self::Error>,
+    {
+        let position = self.stream_position()?;
+        let end = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(position))?;
+        self.retain_range(end.saturating_sub(len)..end)
+    }
+
+    /// Returns the range of an interrupted operation, relative to the original object, if there
+    /// is one.
+    fn pending_retain(&mut self) -> Result<Option<Range<u64>>, Error>
+    where
+        Error: From<Self::Error>,
+    {
+        Ok(pending(&mut Portable::new(self))?.map(|p| p.trailer.start..p.trailer.end))
+    }
+
+    /// Completes an interrupted operation. Returns `false` if there was none.
+    ///
+    /// The position is moved to the start of the object if an operation was completed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::Retain;
+    /// # use std::fs::File;
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let path = dir.path().join("log");
+    /// # std::fs::write(&path, b"").unwrap();
+    /// let mut file = File::options().read(true).write(true).open(path).unwrap();
+    ///
+    /// // Finish the work of a previous run that crashed
+    /// if let Some(range) = file.pending_retain().unwrap() {
+    ///     println!("resuming retaining of {:?}", range);
+    ///     file.resume_retain().unwrap();
+    /// }
+    /// ```
+    fn resume_retain(&mut self) -> Result<bool, Error>
+    where
+        Error: From<Self::Error>,
+    {
+        resume(&mut Portable::new(self))
+    }
+}
+
+/// The operations used to move the data, with the portable implementation in [`Portable`] and
+/// one using file specific operations in [`FileMover`].
+trait Mover {
+    fn len(&mut self) -> Result<u64, Error>;
+    fn position(&mut self) -> Result<u64, Error>;
+    fn set_position(&mut self, position: u64) -> Result<(), Error>;
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error>;
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error>;
+
+    /// Copies `len` bytes from `src` to `dst`, the ranges don't overlap.
+    fn copy(&mut self, src: u64, dst: u64, len: u64) -> Result<(), Error>;
+
+    /// Makes all previous writes persistent.
+    fn sync(&mut self) -> Result<(), Error>;
+
+    /// Attempts to remove the first `len` bytes in a single operation. Returns `false` if this
+    /// isn't supported.
+    fn collapse_front(&mut self, len: u64) -> Result<bool, Error>;
+
+    fn truncate(&mut self, new_len: u64) -> Result<(), Error>;
+}
+
+struct Portable<'a, T: ?Sized> {
+    io: &'a mut T,
+    buf: Vec<u8>,
+}
+
+impl<'a, T> Portable<'a, T>
+where
+    T: ?Sized,
+{
+    fn new(io: &'a mut T) -> Self {
+        Portable {
+            io,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<T> Mover for Portable<'_, T>
+where
+    T: Read + Write + Seek + Truncate + ?Sized,
+    Error: From<T::Error>,
+{
+    fn len(&mut self) -> Result<u64, Error> {
+        self.io.seek(SeekFrom::End(0))
+    }
+
+    fn position(&mut self) -> Result<u64, Error> {
+        self.io.stream_position()
+    }
+
+    fn set_position(&mut self, position: u64) -> Result<(), Error> {
+        self.io.seek(SeekFrom::Start(position)).map(drop)
+    }
+
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        self.io.seek(SeekFrom::Start(offset))?;
+        self.io.read_exact(buf)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error> {
+        self.io.seek(SeekFrom::Start(offset))?;
+        self.io.write_all(buf)
+    }
+
+    fn copy(&mut self, src: u64, dst: u64, len: u64) -> Result<(), Error> {
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.resize(len as usize, 0);
+
+        let res = self
+            .read_at(&mut buf, src)
+            .and_then(|()| self.write_at(&buf, dst));
+        self.buf = buf;
+        res
+    }
+
+    fn sync(&mut self) -> Result<(), Error> {
+        self.io.flush()
+    }
+
+    fn collapse_front(&mut self, _len: u64) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn truncate(&mut self, new_len: u64) -> Result<(), Error> {
+        self.io.truncate_u64(new_len)?;
+        Ok(())
+    }
+}
+
+struct FileMover<'a> {
+    portable: Portable<'a, &'a File>,
+    kernel_copy: bool,
+}
+
+impl<'a> FileMover<'a> {
+    fn new(file: &'a mut &'a File) -> Self {
+        FileMover {
+            portable: Portable::new(file),
+            kernel_copy: true,
+        }
+    }
+
+    fn file(&self) -> &File {
+        self.portable.io
+    }
+}
+
+impl Mover for FileMover<'_> {
+    fn len(&mut self) -> Result<u64, Error> {
+        Ok(self.file().metadata()?.len())
+    }
+
+    fn position(&mut self) -> Result<u64, Error> {
+        self.portable.position()
+    }
+
+    fn set_position(&mut self, position: u64) -> Result<(), Error> {
+        self.portable.set_position(position)
+    }
+
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        self.portable.read_at(buf, offset)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error> {
+        self.portable.write_at(buf, offset)
+    }
+
+    /// Uses `copy_file_range(2)` on Linux, falling back to the portable copy if the filesystem
+    /// doesn't support it.
+    fn copy(&mut self, src: u64, dst: u64, len: u64) -> Result<(), Error> {
+        #[cfg(target_os = "linux")]
+        {
+            if self.kernel_copy {
+                match crate::linux::copy_file_range(self.file(), src, dst, len) {
+                    Ok(()) => return Ok(()),
+                    Err(e) if crate::linux::is_unsupported(&e) => self.kernel_copy = false,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        self.portable.copy(src, dst, len)
+    }
+
+    fn sync(&mut self) -> Result<(), Error> {
+        self.file().sync_data()
+    }
+
+    fn collapse_front(&mut self, len: u64) -> Result<bool, Error> {
+        try_collapse(self.file(), 0, len)
+    }
+
+    fn truncate(&mut self, new_len: u64) -> Result<(), Error> {
+        self.file().set_len(new_len)
+    }
+}
+
+/// The operation described by a trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Trailer {
+    /// The length of the object before the operation, without the trailer.
+    len: u64,
+    start: u64,
+    end: u64,
+    /// The length of each of the two journal slots before the trailer, or zero if the data is
+    /// moved far enough to not need a journal.
+    journal: u32,
+}
+
+impl Trailer {
+    fn new(len: u64, range: Range<u64>) -> Self {
+        let journal = if range.start < MAX_CHUNK {
+            JOURNAL_HEADER_LEN + cmp::min(MAX_CHUNK, range.end - range.start)
+        } else {
+            0
+        };
+
+        Trailer {
+            len,
+            start: range.start,
+            end: range.end,
+            journal: journal as u32,
+        }
+    }
+
+    /// The length of the data that is kept.
+    fn new_len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    fn encode(&self) -> [u8; TRAILER_LEN as usize] {
+        let mut buf = [0; TRAILER_LEN as usize];
+        buf[..8].copy_from_slice(&MAGIC);
+        buf[8..16].copy_from_slice(&self.len.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.start.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.end.to_le_bytes());
+        buf[32..36].copy_from_slice(&self.journal.to_le_bytes());
+        let header_crc = crc(&[&buf[..36]]);
+        buf[36..40].copy_from_slice(&header_crc.to_le_bytes());
+
+        let slot = Trailer::encode_slot(&buf, 0);
+        for &offset in &SLOT_OFFSETS {
+            buf[offset..offset + 12].copy_from_slice(&slot);
+        }
+        buf
+    }
+
+    /// Encodes a progress slot, with a checksum that ties it to the rest of the trailer.
+    fn encode_slot(header: &[u8], progress: u64) -> [u8; 12] {
+        let mut slot = [0; 12];
+        let progress = progress.to_le_bytes();
+        slot[..8].copy_from_slice(&progress);
+        slot[8..].copy_from_slice(&crc(&[&header[..40], &progress]).to_le_bytes());
+        slot
+    }
+
+    /// Decodes a trailer, returning it with the index of the progress slot with the highest
+    /// progress and that progress.
+    fn decode(buf: &[u8; TRAILER_LEN as usize]) -> Option<(Trailer, usize, u64)> {
+        if buf[..8] != MAGIC || crc(&[&buf[..36]]) != u32_at(buf, 36) {
+            return None;
+        }
+
+        let trailer = Trailer {
+            len: u64_at(buf, 8),
+            start: u64_at(buf, 16),
+            end: u64_at(buf, 24),
+            journal: u32_at(buf, 32),
+        };
+        if trailer.start == 0 || trailer.start >= trailer.end || trailer.end > trailer.len {
+            return None;
+        }
+
+        let (slot, progress) = SLOT_OFFSETS
+            .iter()
+            .enumerate()
+            .filter(|&(_, &offset)| {
+                crc(&[&buf[..40], &buf[offset..offset + 8]]) == u32_at(buf, offset + 8)
+            })
+            .map(|(slot, &offset)| (slot, u64_at(buf, offset)))
+            .max_by_key(|&(_, progress)| progress)?;
+
+        Some((trailer, slot, cmp::min(progress, trailer.new_len())))
+    }
+}
+
+fn u64_at(buf: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(buf[i..i + 8].try_into().unwrap())
+}
+
+fn u32_at(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(buf[i..i + 4].try_into().unwrap())
+}
+
+fn crc(parts: &[&[u8]]) -> u32 {
+    let mut crc = Crc32::new();
+    for part in parts {
+        crc.update(part);
+    }
+    crc.finish()
+}
+
+/// The state of an interrupted operation.
+struct Pending {
+    trailer: Trailer,
+    /// The encoded trailer, without the progress slots.
+    header: [u8; 40],
+    /// The offset of the trailer, which moves if the data was collapsed.
+    offset: u64,
+    /// The last progress slot that was written.
+    slot: usize,
+    progress: u64,
+    collapsed: bool,
+}
+
+impl Pending {
+    /// The offset of the first journal slot.
+    fn journal_offset(&self) -> u64 {
+        self.offset - 2 * u64::from(self.trailer.journal)
+    }
+}
+
+fn pending<M>(mover: &mut M) -> Result<Option<Pending>, Error>
+where
+    M: Mover,
+{
+    let len = mover.len()?;
+    let offset = match len.checked_sub(TRAILER_LEN) {
+        Some(offset) => offset,
+        None => return Ok(None),
+    };
+
+    let mut buf = [0; TRAILER_LEN as usize];
+    mover.read_at(&mut buf, offset)?;
+
+    let (trailer, slot, progress) = match Trailer::decode(&buf) {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+
+    // The trailer is only valid at the position where it was written, or where the collapse has
+    // moved it to
+    let data_end = offset.checked_sub(2 * u64::from(trailer.journal));
+    let collapsed = if data_end == Some(trailer.len) {
+        false
+    } else if data_end == Some(trailer.len - trailer.start) {
+        true
+    } else {
+        return Ok(None);
+    };
+
+    let mut header = [0; 40];
+    header.copy_from_slice(&buf[..40]);
+
+    Ok(Some(Pending {
+        trailer,
+        header,
+        offset,
+        slot,
+        progress,
+        collapsed,
+    }))
+}
+
+fn retain<M>(mover: &mut M, range: Range<u64>) -> Result<(), Error>
+where
+    M: Mover,
+{
+    let position = mover.position()?;
+    if pending(mover)?.is_some() {
+        return Err(Error::other(
+            "an interrupted retain operation has to be resumed first",
+        ));
+    }
+
+    let len = mover.len()?;
+    if range.end > len {
+        return Err(TruncateError::GrowNotSupported {
+            requested: range.end,
+            current: len,
+        }
+        .into());
+    }
+    if range.start > range.end {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "range starts after its end",
+        ));
+    }
+
+    let new_len = range.end - range.start;
+
+    // Nothing to move, so a single truncation is enough
+    if range.start == 0 || new_len == 0 {
+        mover.truncate(new_len)?;
+    } else {
+        let trailer = Trailer::new(len, range.clone());
+        let offset = len + 2 * u64::from(trailer.journal);
+        let buf = trailer.encode();
+
+        mover.write_at(&buf, offset)?;
+        mover.sync()?;
+
+        let mut header = [0; 40];
+        header.copy_from_slice(&buf[..40]);
+        run(
+            mover,
+            Pending {
+                trailer,
+                header,
+                offset,
+                slot: 0,
+                progress: 0,
+                collapsed: false,
+            },
+        )?;
+    }
+
+    mover.set_position(cmp::min(position.saturating_sub(range.start), new_len))
+}
+
+fn resume<M>(mover: &mut M) -> Result<bool, Error>
+where
+    M: Mover,
+{
+    match pending(mover)? {
+        Some(pending) => {
+            run(mover, pending)?;
+            mover.set_position(0)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn run<M>(mover: &mut M, mut state: Pending) -> Result<(), Error>
+where
+    M: Mover,
+{
+    let journaled = if state.trailer.journal != 0 {
+        read_journal(mover, &state)?
+    } else {
+        None
+    };
+
+    // Collapsing is only possible if nothing was copied yet
+    let copied = state.progress != 0 || journaled.is_some();
+    if !state.collapsed && !copied && mover.collapse_front(state.trailer.start)? {
+        state.collapsed = true;
+    }
+
+    if !state.collapsed {
+        if state.trailer.journal != 0 {
+            copy_journaled(mover, &state, journaled)?;
+        } else {
+            copy_checkpointed(mover, &mut state)?;
+        }
+    }
+
+    mover.sync()?;
+    mover.truncate(state.trailer.new_len())
+}
+
+/// Copies the data with the progress recorded in the trailer, which is safe if the data is moved
+/// by at least `MAX_CHUNK` bytes.
+fn copy_checkpointed<M>(mover: &mut M, state: &mut Pending) -> Result<(), Error>
+where
+    M: Mover,
+{
+    let start = state.trailer.start;
+    let new_len = state.trailer.new_len();
+    let checkpoint = cmp::min(start, MAX_CHECKPOINT);
+
+    let mut copied = state.progress;
+    while copied < new_len {
+        let target = cmp::min(state.progress + checkpoint, new_len);
+        while copied < target {
+            let n = cmp::min(MAX_CHUNK, target - copied);
+            mover.copy(start + copied, copied, n)?;
+            copied += n;
+        }
+
+        if copied < new_len {
+            // The data has to be persistent before the progress is, and the progress before the
+            // copy gets further ahead of it than `start`
+            mover.sync()?;
+            state.slot = 1 - state.slot;
+            let slot = Trailer::encode_slot(&state.header, copied);
+            mover.write_at(&slot, state.offset + SLOT_OFFSETS[state.slot] as u64)?;
+            mover.sync()?;
+            state.progress = copied;
+        }
+    }
+
+    Ok(())
+}
+
+/// A step of a journaled copy: the slot, the offset the data is copied to and the data.
+type Step = (usize, u64, Vec<u8>);
+
+/// Returns the valid journal slot with the highest progress.
+fn read_journal<M>(mover: &mut M, state: &Pending) -> Result<Option<Step>, Error>
+where
+    M: Mover,
+{
+    let slot_len = u64::from(state.trailer.journal);
+    let mut latest: Option<Step> = None;
+
+    for slot in 0..2 {
+        let offset = state.journal_offset() + slot as u64 * slot_len;
+        let mut header = [0; JOURNAL_HEADER_LEN as usize];
+        mover.read_at(&mut header, offset)?;
+
+        let progress = u64_at(&header, 0);
+        let len = u64::from(u32_at(&header, 8));
+        if len > slot_len - JOURNAL_HEADER_LEN || progress + len > state.trailer.new_len() {
+            continue;
+        }
+
+        let mut data = vec![0; len as usize];
+        mover.read_at(&mut data, offset + JOURNAL_HEADER_LEN)?;
+        if crc(&[&state.header, &header[..12], &data]) != u32_at(&header, 12) {
+            continue;
+        }
+
+        if latest.as_ref().is_none_or(|&(_, p, _)| progress > p) {
+            latest = Some((slot, progress, data));
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Copies the data in steps that are written to the journal first.
+fn copy_journaled<M>(mover: &mut M, state: &Pending, journaled: Option<Step>) -> Result<(), Error>
+where
+    M: Mover,
+{
+    let start = state.trailer.start;
+    let new_len = state.trailer.new_len();
+    let slot_len = u64::from(state.trailer.journal);
+
+    let (mut slot, mut copied) = match journaled {
+        Some((slot, progress, data)) => {
+            mover.write_at(&data, progress)?;
+            (slot, progress + data.len() as u64)
+        }
+        None => (1, 0),
+    };
+
+    let mut buf = vec![0; (slot_len - JOURNAL_HEADER_LEN) as usize];
+    while copied < new_len {
+        let n = cmp::min(buf.len() as u64, new_len - copied) as usize;
+        let data = &mut buf[..n];
+        mover.read_at(data, start + copied)?;
+
+        let mut header = [0; JOURNAL_HEADER_LEN as usize];
+        header[..8].copy_from_slice(&copied.to_le_bytes());
+        header[8..12].copy_from_slice(&(n as u32).to_le_bytes());
+        let step_crc = crc(&[&state.header, &header[..12], data]);
+        header[12..].copy_from_slice(&step_crc.to_le_bytes());
+
+        // The previous step has to be persistent before the journal doesn't contain it anymore,
+        // and this one before the data is overwritten
+        mover.sync()?;
+        slot = 1 - slot;
+        let offset = state.journal_offset() + slot as u64 * slot_len;
+        mover.write_at(&header, offset)?;
+        mover.write_at(data, offset + JOURNAL_HEADER_LEN)?;
+        mover.sync()?;
+
+        mover.write_at(data, copied)?;
+        copied += n as u64;
+    }
+
+    Ok(())
+}
+
+impl Retain for File {
+    fn retain_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        (&*self).retain_range(range)
+    }
+
+    fn pending_retain(&mut self) -> Result<Option<Range<u64>>, Error> {
+        (&*self).pending_retain()
+    }
+
+    fn resume_retain(&mut self) -> Result<bool, Error> {
+        (&*self).resume_retain()
+    }
+}
+
+/// On Linux, the data is moved with `FALLOC_FL_COLLAPSE_RANGE` if the filesystem supports it and
+/// the start of the range is a multiple of the block size, otherwise it is copied with
+/// `copy_file_range(2)` where possible. The file is synced as required to resume the operation
+/// after a crash.
+///
+/// Files opened in append mode can't be written at an offset, so on Unix, `retain_range` and
+/// `resume_retain` fail with an error of kind [`InvalidInput`](ErrorKind::InvalidInput) for them
+/// before modifying the file.
+impl Retain for &File {
+    fn retain_range(&mut self, range: Range<u64>) -> Result<(), Error> {
+        check_not_append(self)?;
+        let mut file: &File = self;
+        retain(&mut FileMover::new(&mut file), range)
+    }
+
+    fn pending_retain(&mut self) -> Result<Option<Range<u64>>, Error> {
+        let mut file: &File = self;
+        let pending = pending(&mut FileMover::new(&mut file))?;
+        Ok(pending.map(|p| p.trailer.start..p.trailer.end))
+    }
+
+    fn resume_retain(&mut self) -> Result<bool, Error> {
+        check_not_append(self)?;
+        let mut file: &File = self;
+        resume(&mut FileMover::new(&mut file))
+    }
+}
+
+impl<T> Retain for Cursor<T>
+where
+    Cursor<T>: Read + Write + Seek,
+    T: Truncate,
+{
+}
+
+impl<T> Retain for &mut T
+where
+    T: Retain,
+{
+    fn retain_range(&mut self, range: Range<u64>) -> Result<(), Error>
+    where
+        Error: From<T::Error>,
+    {
+        (**self).retain_range(range)
+    }
+
+    fn pending_retain(&mut self) -> Result<Option<Range<u64>>, Error>
+    where
+        Error: From<T::Error>,
+    {
+        (**self).pending_retain()
+    }
+
+    fn resume_retain(&mut self) -> Result<bool, Error>
+    where
+        Error: From<T::Error>,
+    {
+        (**self).resume_retain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retain, Mover, Portable, Retain, Trailer, MAX_CHUNK, SLOT_OFFSETS};
+    use crate::{
+        copy::fixtures::{self, data, read_all},
+        Len,
+    };
+    use std::{
+        fs,
+        io::{Cursor, Error, ErrorKind, Seek, Write},
+        ops::Range,
+    };
+
+    /// Fails after a number of modifications, like a crash would.
+    struct Failing<M> {
+        mover: M,
+        remaining: usize,
+    }
+
+    impl<M> Failing<M> {
+        fn modify(&mut self) -> Result<(), Error> {
+            match self.remaining.checked_sub(1) {
+                Some(n) => {
+                    self.remaining = n;
+                    Ok(())
+                }
+                None => Err(Error::other("crash")),
+            }
+        }
+    }
+
+    impl<M> Mover for Failing<M>
+    where
+        M: Mover,
+    {
+        fn len(&mut self) -> Result<u64, Error> {
+            self.mover.len()
+        }
+
+        fn position(&mut self) -> Result<u64, Error> {
+            self.mover.position()
+        }
+
+        fn set_position(&mut self, position: u64) -> Result<(), Error> {
+            self.mover.set_position(position)
+        }
+
+        fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+            self.mover.read_at(buf, offset)
+        }
+
+        fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error> {
+            self.modify()?;
+            self.mover.write_at(buf, offset)
+        }
+
+        fn copy(&mut self, src: u64, dst: u64, len: u64) -> Result<(), Error> {
+            self.modify()?;
+            self.mover.copy(src, dst, len)
+        }
+
+        fn sync(&mut self) -> Result<(), Error> {
+            self.mover.sync()
+        }
+
+        fn collapse_front(&mut self, len: u64) -> Result<bool, Error> {
+            self.mover.collapse_front(len)
+        }
+
+        fn truncate(&mut self, new_len: u64) -> Result<(), Error> {
+            self.modify()?;
+            self.mover.truncate(new_len)
+        }
+    }
+
+    /// Interrupts retaining `range` at every possible point, then resumes it.
+    fn interrupt(len: u64, range: Range<u64>) {
+        let orig = data(len);
+        let expected = &orig[range.start as usize..range.end as usize];
+
+        for remaining in 0.. {
+            let mut c = Cursor::new(orig.clone());
+            let mut mover = Failing {
+                mover: Portable::new(&mut c),
+                remaining,
+            };
+            if retain(&mut mover, range.clone()).is_ok() {
+                assert_eq!(c.get_ref(), expected);
+                break;
+            }
+
+            match c.pending_retain().unwrap() {
+                Some(pending) => {
+                    assert_eq!(pending, range);
+                    let e = c.retain_tail(1).unwrap_err();
+                    assert_eq!(e.kind(), ErrorKind::Other);
+
+                    assert!(c.resume_retain().unwrap());
+                    assert!(c.get_ref() == expected, "interrupted after {}", remaining);
+                }
+                None => assert!(c.get_ref() == &orig),
+            }
+            assert!(!c.resume_retain().unwrap());
+        }
+    }
+
+    #[test]
+    fn cursor() {
+        let mut c = Cursor::new(data(100));
+        c.set_position(50);
+        c.retain_range(10..60).unwrap();
+        assert_eq!(c.get_ref(), &data(100)[10..60]);
+        assert_eq!(c.position(), 40);
+
+        c.retain_tail(20).unwrap();
+        assert_eq!(c.get_ref(), &data(100)[40..60]);
+        assert_eq!(c.position(), 10);
+
+        // Keeping the start or nothing only truncates
+        c.retain_range(0..5).unwrap();
+        assert_eq!(c.get_ref(), &data(100)[40..45]);
+        c.retain_tail(10).unwrap();
+        assert_eq!(c.get_ref().len(), 5);
+        c.retain_range(3..3).unwrap();
+        assert!(c.get_ref().is_empty());
+    }
+
+    #[test]
+    fn invalid_range() {
+        let mut c = Cursor::new(data(10));
+        let e = c.retain_range(5..11).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        #[allow(clippy::reversed_empty_ranges)]
+        let e = c.retain_range(6..5).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(c.get_ref(), &data(10));
+    }
+
+    #[test]
+    fn journaled() {
+        interrupt(100, 3..90);
+        interrupt(3 * MAX_CHUNK + 7, 1000..3 * MAX_CHUNK);
+    }
+
+    #[test]
+    fn checkpointed() {
+        interrupt(3 * MAX_CHUNK + 7, MAX_CHUNK..3 * MAX_CHUNK + 7);
+        interrupt(5 * MAX_CHUNK, MAX_CHUNK + 1..4 * MAX_CHUNK);
+    }
+
+    #[test]
+    fn trailer() {
+        let t = Trailer::new(100, 10..50);
+        let mut buf = t.encode();
+        let decoded = Trailer::decode(&buf).map(|(t, _, progress)| (t, progress));
+        assert_eq!(decoded, Some((t, 0)));
+
+        let slot = Trailer::encode_slot(&buf, 20);
+        buf[SLOT_OFFSETS[1]..SLOT_OFFSETS[1] + 12].copy_from_slice(&slot);
+        assert_eq!(Trailer::decode(&buf), Some((t, 1, 20)));
+
+        // A torn slot is ignored
+        buf[SLOT_OFFSETS[1]] ^= 1;
+        assert_eq!(Trailer::decode(&buf), Some((t, 0, 0)));
+
+        buf[20] ^= 1;
+        assert_eq!(Trailer::decode(&buf), None);
+    }
+
+    #[test]
+    fn no_trailer() {
+        // Data that looks like a trailer at the wrong position isn't one
+        let t = Trailer::new(10, 1..5);
+        let mut c = Cursor::new(t.encode().to_vec());
+        assert_eq!(c.pending_retain().unwrap(), None);
+        c.retain_tail(4).unwrap();
+        assert_eq!(c.get_ref().len(), 4);
+    }
+
+    #[test]
+    fn file() {
+        fixtures::files(|mut f| {
+            let len = 2 * MAX_CHUNK + 100;
+            f.write_all(&data(len)).unwrap();
+
+            f.retain_range(3..len - 10).unwrap();
+            assert_eq!(Len::len(&f).unwrap(), len - 13);
+            assert_eq!(f.stream_position().unwrap(), len - 13);
+
+            // Moved far enough to not need the journal
+            (&f).retain_tail(MAX_CHUNK - 5).unwrap();
+            let expected = &data(len)[(len - 10 - MAX_CHUNK + 5) as usize..(len - 10) as usize];
+            assert!(read_all(&f) == expected);
+            assert_eq!(f.pending_retain().unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn file_aligned() {
+        // Collapsed if the filesystem supports it
+        fixtures::files(|mut f| {
+            f.write_all(&data(64 * 1024)).unwrap();
+
+            f.retain_range(16 * 1024..60 * 1024).unwrap();
+            assert_eq!(Len::len(&f).unwrap(), 44 * 1024);
+            assert!(read_all(&f) == data(64 * 1024)[16 * 1024..60 * 1024]);
+        });
+    }
+
+    #[test]
+    fn file_resume() {
+        let mut c = Cursor::new(data(10_000));
+        let mut mover = Failing {
+            mover: Portable::new(&mut c),
+            remaining: 3,
+        };
+        retain(&mut mover, 100..10_000).unwrap_err();
+
+        fixtures::files(|mut f| {
+            f.write_all(c.get_ref()).unwrap();
+
+            assert_eq!(f.pending_retain().unwrap(), Some(100..10_000));
+            assert!(f.resume_retain().unwrap());
+            assert!(read_all(&f) == data(10_000)[100..]);
+            assert!(!f.resume_retain().unwrap());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_append() {
+        let (named, mut f) = fixtures::append_file();
+
+        let e = f.retain_range(3..8).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        let e = f.resume_retain().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(fs::read(named.path()).unwrap(), b"0123456789");
+    }
+}
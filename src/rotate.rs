@@ -0,0 +1,301 @@
+//! Size-based rotating file writer.
+
+use crate::{Truncate, TruncateFront};
+use std::{
+    cmp,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// What a [`RotatingWriter`] does once the file reaches its maximum size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    /// Rename the file to `<path>.1` and create a new one in its place.
+    ///
+    /// Older generations are shifted to `<path>.2`, `<path>.3` and so on, up to `keep`
+    /// generations are retained. With `keep` set to zero, the old file is deleted.
+    Rename {
+        /// The number of old generations to retain.
+        keep: usize,
+    },
+    /// Copy the file to `<path>.1` and truncate it to zero length, keeping the same file open.
+    ///
+    /// This is the `copytruncate` strategy of logrotate, useful when other processes hold the
+    /// file open. Generations are retained as with [`Rename`](Rotation::Rename). Data written
+    /// between the copy and the truncation by other processes is lost.
+    CopyTruncate {
+        /// The number of old generations to retain.
+        keep: usize,
+    },
+    /// Discard everything but the last given number of bytes of the file using
+    /// [`TruncateFront`]. No old generations are retained.
+    ///
+    /// The number of bytes should be smaller than the maximum size of the writer, otherwise the
+    /// file is never shortened.
+    KeepLast(u64),
+}
+
+/// A writer that rotates a file once it grows beyond a maximum size.
+///
+/// Before each write, the writer checks if the write would make the file exceed its maximum size,
+/// and rotates it according to the configured [`Rotation`] if it does. A single write larger than
+/// the maximum size is written to the file in full after rotating.
+///
+/// The size is tracked by the writer itself, changes to the file made by other processes are not
+/// accounted for.
+///
+/// If creating the new file fails after the old one was renamed, the error is returned and the
+/// next write or rotation tries to create it again. Nothing is written to the renamed file.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{RotatingWriter, Rotation};
+/// # use std::io::Write;
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("app.log");
+///
+/// let mut w = RotatingWriter::open(&path, 8, Rotation::Rename { keep: 1 }).unwrap();
+/// w.write_all(b"first\n").unwrap();
+/// w.write_all(b"second\n").unwrap();
+///
+/// assert_eq!(std::fs::read(&path).unwrap(), b"second\n");
+/// assert_eq!(std::fs::read(dir.path().join("app.log.1")).unwrap(), b"first\n");
+/// ```
+#[derive(Debug)]
+pub struct RotatingWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    rotation: Rotation,
+    /// Set while `file` was renamed by a rotation, but the new file wasn't created yet.
+    reopen: bool,
+}
+
+impl RotatingWriter {
+    /// Opens or creates the file at `path` for writing, appending to existing contents.
+    pub fn open<P>(path: P, max_size: u64, rotation: Rotation) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_owned();
+
+        // Not opened in append mode, since TruncateFront needs to write to the start of the file
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let size = file.seek(SeekFrom::End(0))?;
+
+        Ok(RotatingWriter {
+            path,
+            file,
+            size,
+            max_size,
+            rotation,
+            reopen: false,
+        })
+    }
+
+    /// Returns the path of the current file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the current size of the file.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns a reference to the current file.
+    ///
+    /// This is the renamed old file if creating the new one failed during the last rotation.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Rotates the file immediately, regardless of its size.
+    pub fn rotate(&mut self) -> Result<(), Error> {
+        match self.rotation {
+            Rotation::Rename { keep } => {
+                // Only retry creating the file if the last rotation already moved the old one
+                if !self.reopen {
+                    shift_generations(&self.path, keep)?;
+                    if keep > 0 {
+                        fs::rename(&self.path, generation(&self.path, 1))?;
+                    } else {
+                        fs::remove_file(&self.path)?;
+                    }
+                    self.reopen = true;
+                }
+
+                self.file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&self.path)?;
+                self.size = 0;
+                self.reopen = false;
+            }
+            Rotation::CopyTruncate { keep } => {
+                shift_generations(&self.path, keep)?;
+                if keep > 0 {
+                    fs::copy(&self.path, generation(&self.path, 1))?;
+                }
+
+                self.file.clear()?;
+                self.file.seek(SeekFrom::Start(0))?;
+                self.size = 0;
+            }
+            Rotation::KeepLast(n) => {
+                self.file.truncate_front(cmp::min(n, self.size))?;
+                self.size = self.file.seek(SeekFrom::End(0))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if self.reopen
+            || self.size > 0 && self.size.saturating_add(buf.len() as u64) > self.max_size
+        {
+            self.rotate()?;
+        }
+
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush()
+    }
+}
+
+/// Returns the path of the given old generation, `<path>.<n>`.
+fn generation(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path);
+    name.push(format!(".{}", n));
+    name.into()
+}
+
+/// Makes room for a new first generation by shifting the existing ones back, deleting the oldest
+/// one if there are already `keep` generations.
+fn shift_generations(path: &Path, keep: usize) -> Result<(), Error> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    match fs::remove_file(generation(path, keep)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    for n in (1..keep).rev() {
+        match fs::rename(generation(path, n), generation(path, n + 1)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generation, RotatingWriter, Rotation};
+    use std::{fs, io::Write};
+
+    #[test]
+    fn rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut w = RotatingWriter::open(&path, 4, Rotation::Rename { keep: 2 }).unwrap();
+        for chunk in [&b"aaa"[..], b"bbb", b"ccc", b"ddd"].iter() {
+            w.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), b"ddd");
+        assert_eq!(fs::read(generation(&path, 1)).unwrap(), b"ccc");
+        assert_eq!(fs::read(generation(&path, 2)).unwrap(), b"bbb");
+        assert!(!generation(&path, 3).exists());
+    }
+
+    #[test]
+    fn rename_keep_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut w = RotatingWriter::open(&path, 4, Rotation::Rename { keep: 0 }).unwrap();
+        w.write_all(b"aaa").unwrap();
+        w.write_all(b"bbb").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"bbb");
+        assert!(!generation(&path, 1).exists());
+    }
+
+    #[test]
+    fn rename_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut w = RotatingWriter::open(&path, 4, Rotation::Rename { keep: 2 }).unwrap();
+        w.write_all(b"aaa").unwrap();
+
+        // The state after creating the new file failed, with a directory still in the way
+        fs::rename(&path, generation(&path, 1)).unwrap();
+        w.reopen = true;
+        fs::create_dir(&path).unwrap();
+
+        w.write_all(b"b").unwrap_err();
+        assert_eq!(fs::read(generation(&path, 1)).unwrap(), b"aaa");
+
+        // Retried without rotating again
+        fs::remove_dir(&path).unwrap();
+        w.write_all(b"b").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"b");
+        assert_eq!(fs::read(generation(&path, 1)).unwrap(), b"aaa");
+        assert!(!generation(&path, 2).exists());
+    }
+
+    #[test]
+    fn copy_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        fs::write(&path, b"aa").unwrap();
+
+        let mut w = RotatingWriter::open(&path, 4, Rotation::CopyTruncate { keep: 1 }).unwrap();
+        assert_eq!(w.size(), 2);
+        w.write_all(b"bb").unwrap();
+        w.write_all(b"cc").unwrap();
+        w.write_all(b"ddd").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"ddd");
+        assert_eq!(fs::read(generation(&path, 1)).unwrap(), b"cc");
+        assert_eq!(w.get_ref().metadata().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn keep_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut w = RotatingWriter::open(&path, 8, Rotation::KeepLast(3)).unwrap();
+        w.write_all(b"012345").unwrap();
+        w.write_all(b"6789").unwrap();
+        assert_eq!(w.size(), 7);
+
+        assert_eq!(fs::read(&path).unwrap(), b"3456789");
+        assert!(!generation(&path, 1).exists());
+    }
+}
@@ -0,0 +1,213 @@
+//! Nested savepoints for append-only objects.
+
+use crate::{Len, Truncate};
+use std::{
+    io::{Error, ErrorKind},
+    ops::{Deref, DerefMut},
+};
+
+/// Identifies a savepoint created by [`Savepoints::savepoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SavepointId(u64);
+
+/// A wrapper providing nested savepoints for objects that are only appended to.
+///
+/// This works like savepoints in SQL: [`savepoint`](Savepoints::savepoint) records the current
+/// length of the object, [`rollback_to`](Savepoints::rollback_to) truncates the object back to it
+/// and [`release`](Savepoints::release) forgets it without changing the object. Savepoints are
+/// nested, rolling back to or releasing a savepoint also discards all savepoints created after it.
+///
+/// The wrapper dereferences to the wrapped object, which can be used to append data. Rolling back
+/// doesn't change the seek position of the object apart from what its [`Truncate`] impl does, so
+/// files should be opened in append mode to keep writing at the new end.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::Savepoints;
+/// # use std::io::Write;
+/// let mut s = Savepoints::new(Vec::new());
+///
+/// let outer = s.savepoint().unwrap();
+/// s.write_all(b"outer").unwrap();
+///
+/// let inner = s.savepoint().unwrap();
+/// s.write_all(b" inner").unwrap();
+///
+/// s.rollback_to(inner).unwrap();
+/// assert_eq!(s.get_ref(), b"outer");
+///
+/// s.release(outer).unwrap();
+/// assert_eq!(s.into_inner(), b"outer");
+/// ```
+#[derive(Debug)]
+pub struct Savepoints<T> {
+    inner: T,
+    stack: Vec<(SavepointId, u64)>,
+    next_id: u64,
+}
+
+impl<T> Savepoints<T>
+where
+    T: Truncate + Len,
+    Error: From<<T as Truncate>::Error> + From<<T as Len>::Error>,
+{
+    /// Wraps the given object, with no active savepoints.
+    pub fn new(inner: T) -> Self {
+        Savepoints {
+            inner,
+            stack: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Creates a new savepoint at the current length of the object.
+    pub fn savepoint(&mut self) -> Result<SavepointId, Error> {
+        let len = self.inner.len()?;
+        let id = SavepointId(self.next_id);
+
+        self.next_id += 1;
+        self.stack.push((id, len));
+        Ok(id)
+    }
+
+    /// Truncates the object back to the length it had when the savepoint was created.
+    ///
+    /// All savepoints created after `id` are discarded, `id` itself stays active and can be
+    /// rolled back to again. The object is truncated with a single call to
+    /// [`Truncate::truncate_u64`], if that fails the savepoints are left unchanged.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the savepoint is not
+    /// active.
+    pub fn rollback_to(&mut self, id: SavepointId) -> Result<(), Error> {
+        let index = self.index_of(id)?;
+
+        self.inner.truncate_u64(self.stack[index].1)?;
+        self.stack.truncate(index + 1);
+        Ok(())
+    }
+
+    /// Discards the savepoint and all savepoints created after it, keeping the data written since.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the savepoint is not
+    /// active.
+    pub fn release(&mut self, id: SavepointId) -> Result<(), Error> {
+        let index = self.index_of(id)?;
+
+        self.stack.truncate(index);
+        Ok(())
+    }
+
+    /// Returns the number of active savepoints.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn index_of(&self, id: SavepointId) -> Result<usize, Error> {
+        self.stack
+            .iter()
+            .position(|(i, _)| *i == id)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "savepoint is not active"))
+    }
+}
+
+impl<T> Savepoints<T> {
+    /// Returns a reference to the wrapped object.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped object.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped object, discarding all savepoints without changing it.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Deref for Savepoints<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Savepoints<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Savepoints;
+    use crate::Len;
+    use std::io::{Cursor, ErrorKind, Write};
+
+    #[test]
+    fn vec() {
+        let mut s = Savepoints::new(Vec::new());
+
+        let a = s.savepoint().unwrap();
+        s.extend_from_slice(&[0, 1]);
+        let b = s.savepoint().unwrap();
+        s.extend_from_slice(&[2, 3]);
+        let c = s.savepoint().unwrap();
+        s.extend_from_slice(&[4]);
+        assert_eq!(s.depth(), 3);
+
+        // Discards c, keeps b
+        s.rollback_to(b).unwrap();
+        assert_eq!(s.get_ref(), &[0, 1]);
+        assert_eq!(s.depth(), 2);
+        let e = s.rollback_to(c).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        // b can be rolled back to repeatedly
+        s.push(5);
+        s.rollback_to(b).unwrap();
+        assert_eq!(s.get_ref(), &[0, 1]);
+
+        s.push(6);
+        s.release(b).unwrap();
+        assert_eq!(s.depth(), 1);
+        assert_eq!(s.get_ref(), &[0, 1, 6]);
+        let e = s.release(b).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        s.rollback_to(a).unwrap();
+        assert!(s.get_ref().is_empty());
+    }
+
+    #[test]
+    fn cursor() {
+        let mut s = Savepoints::new(Cursor::new(vec![0, 1]));
+        s.set_position(2);
+
+        let a = s.savepoint().unwrap();
+        s.write_all(&[2, 3]).unwrap();
+        s.rollback_to(a).unwrap();
+        assert_eq!(s.get_ref().get_ref(), &[0, 1]);
+        assert_eq!(s.position(), 2);
+    }
+
+    #[test]
+    fn file() {
+        let mut s = Savepoints::new(tempfile::tempfile().unwrap());
+
+        s.write_all(&[0, 1]).unwrap();
+        let a = s.savepoint().unwrap();
+        s.write_all(&[2, 3]).unwrap();
+        let b = s.savepoint().unwrap();
+        s.write_all(&[4, 5]).unwrap();
+
+        s.rollback_to(b).unwrap();
+        assert_eq!(Len::len(s.get_ref()).unwrap(), 4);
+        s.rollback_to(a).unwrap();
+        assert_eq!(Len::len(s.get_ref()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,416 @@
+//! Size expressions like `64MiB` or `-4K`, as used by the `truncate` command.
+
+use core::{fmt, num::NonZeroU64, str::FromStr};
+
+/// A target length, either absolute or relative to the current length.
+///
+/// The syntax is the one of the `--size` option of GNU `truncate`: An integer, optionally
+/// followed by a unit and optionally prefixed by one of the modifiers described for the variants.
+///
+/// Units are `K`, `M`, `G`, `T`, `P`, `E`, `Z` and `Y` for powers of 1024 (IEC), which may also
+/// be written as `KiB`, `MiB` and so on. `KB`, `MB` and so on are powers of 1000 (SI). The unit
+/// letter is case-insensitive.
+///
+/// # Example
+///
+/// ```
+/// # use io_truncate::{Len, SizeSpec, Truncate};
+/// let mut file = tempfile::tempfile().unwrap();
+/// file.set_len(5000).unwrap();
+///
+/// let spec: SizeSpec = "/4K".parse().unwrap();
+/// assert_eq!(spec, SizeSpec::RoundDown(std::num::NonZeroU64::new(4096).unwrap()));
+///
+/// file.truncate_u64(spec.apply(file.len().unwrap())).unwrap();
+/// assert_eq!(file.len().unwrap(), 4096);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SizeSpec {
+    /// A fixed length, without a prefix.
+    Absolute(u64),
+    /// Extend by the given number of bytes, prefixed by `+`.
+    Extend(u64),
+    /// Reduce by the given number of bytes, prefixed by `-`.
+    Reduce(u64),
+    /// At most the given length, prefixed by `<`.
+    AtMost(u64),
+    /// At least the given length, prefixed by `>`.
+    AtLeast(u64),
+    /// Round down to a multiple of the given size, prefixed by `/`.
+    RoundDown(NonZeroU64),
+    /// Round up to a multiple of the given size, prefixed by `%`.
+    RoundUp(NonZeroU64),
+}
+
+impl SizeSpec {
+    /// Computes the new length for an object with the given current length.
+    ///
+    /// Results that don't fit into a `u64` saturate: Extending is capped at `u64::MAX`, rounding
+    /// up at the largest multiple below it. Reducing by more than the current length results in
+    /// zero.
+    pub fn apply(&self, current_len: u64) -> u64 {
+        match *self {
+            SizeSpec::Absolute(len) => len,
+            SizeSpec::Extend(n) => current_len.saturating_add(n),
+            SizeSpec::Reduce(n) => current_len.saturating_sub(n),
+            SizeSpec::AtMost(len) => current_len.min(len),
+            SizeSpec::AtLeast(len) => current_len.max(len),
+            SizeSpec::RoundDown(m) => current_len / m.get() * m.get(),
+            SizeSpec::RoundUp(m) => {
+                let m = m.get();
+                match current_len.checked_add(m - 1) {
+                    Some(n) => n / m * m,
+                    None => u64::MAX / m * m,
+                }
+            }
+        }
+    }
+
+    /// Computes the new length like [`apply`](SizeSpec::apply), but returns `None` if extending
+    /// or rounding up results in a length that doesn't fit into a `u64`.
+    ///
+    /// Reducing by more than the current length still results in zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::SizeSpec;
+    /// assert_eq!(SizeSpec::Extend(10).checked_apply(100), Some(110));
+    /// assert_eq!(SizeSpec::Extend(10).checked_apply(u64::MAX), None);
+    /// ```
+    pub fn checked_apply(&self, current_len: u64) -> Option<u64> {
+        match *self {
+            SizeSpec::Extend(n) => current_len.checked_add(n),
+            SizeSpec::RoundUp(m) => match current_len % m.get() {
+                0 => Some(current_len),
+                rem => current_len.checked_add(m.get() - rem),
+            },
+            _ => Some(self.apply(current_len)),
+        }
+    }
+
+    /// Returns whether the result of [`apply`](SizeSpec::apply) depends on the current length,
+    /// which is the case for every variant except [`Absolute`](SizeSpec::Absolute).
+    pub fn is_relative(&self) -> bool {
+        !matches!(self, SizeSpec::Absolute(_))
+    }
+
+    fn parts(&self) -> (&'static str, u64) {
+        match *self {
+            SizeSpec::Absolute(n) => ("", n),
+            SizeSpec::Extend(n) => ("+", n),
+            SizeSpec::Reduce(n) => ("-", n),
+            SizeSpec::AtMost(n) => ("<", n),
+            SizeSpec::AtLeast(n) => (">", n),
+            SizeSpec::RoundDown(n) => ("/", n.get()),
+            SizeSpec::RoundUp(n) => ("%", n.get()),
+        }
+    }
+}
+
+/// Formats the size in a form that can be parsed again, using the largest IEC unit that
+/// represents it exactly, for example `+64MiB`.
+impl fmt::Display for SizeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, mut value) = self.parts();
+
+        let mut unit = 0;
+        while value != 0 && value % 1024 == 0 && unit < UNITS.len() {
+            value /= 1024;
+            unit += 1;
+        }
+
+        match unit {
+            0 => write!(f, "{}{}", prefix, value),
+            _ => write!(f, "{}{}{}iB", prefix, value, UNITS[unit - 1] as char),
+        }
+    }
+}
+
+impl FromStr for SizeSpec {
+    type Err = ParseSizeError;
+
+    fn from_str(s: &str) -> Result<Self, ParseSizeError> {
+        let (prefix, rest) = match s.as_bytes().first() {
+            Some(b'+' | b'-' | b'<' | b'>' | b'/' | b'%') => s.split_at(1),
+            _ => ("", s),
+        };
+
+        let value = parse_value(rest)?;
+        let non_zero = || NonZeroU64::new(value).ok_or(ParseSizeError(ErrorKind::Zero));
+
+        Ok(match prefix {
+            "+" => SizeSpec::Extend(value),
+            "-" => SizeSpec::Reduce(value),
+            "<" => SizeSpec::AtMost(value),
+            ">" => SizeSpec::AtLeast(value),
+            "/" => SizeSpec::RoundDown(non_zero()?),
+            "%" => SizeSpec::RoundUp(non_zero()?),
+            _ => SizeSpec::Absolute(value),
+        })
+    }
+}
+
+/// The unit letters, in order of their exponent.
+const UNITS: &[u8] = b"KMGTPEZY";
+
+/// Parses a number with an optional unit.
+fn parse_value(s: &str) -> Result<u64, ParseSizeError> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if digits == 0 {
+        return Err(ParseSizeError(ErrorKind::Invalid));
+    }
+
+    // Only the digits are left, so this can only fail on overflow
+    let value: u64 = s[..digits]
+        .parse()
+        .map_err(|_| ParseSizeError(ErrorKind::TooLarge))?;
+
+    let (unit, base) = match &s.as_bytes()[digits..] {
+        [] => return Ok(value),
+        [unit] | [unit, b'i', b'B'] => (unit.to_ascii_uppercase(), 1024u64),
+        [unit, b'B'] => (unit.to_ascii_uppercase(), 1000),
+        _ => return Err(ParseSizeError(ErrorKind::Invalid)),
+    };
+    let exponent = match UNITS.iter().position(|&u| u == unit) {
+        Some(i) => i as u32 + 1,
+        None => return Err(ParseSizeError(ErrorKind::Invalid)),
+    };
+
+    base.checked_pow(exponent)
+        .and_then(|factor| value.checked_mul(factor))
+        .ok_or(ParseSizeError(ErrorKind::TooLarge))
+}
+
+/// The error returned when parsing a [`SizeSpec`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSizeError(ErrorKind);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    Invalid,
+    TooLarge,
+    Zero,
+}
+
+impl fmt::Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.0 {
+            ErrorKind::Invalid => "invalid size",
+            ErrorKind::TooLarge => "size too large",
+            ErrorKind::Zero => "cannot round to a multiple of zero",
+        })
+    }
+}
+
+impl core::error::Error for ParseSizeError {}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::SizeSpec;
+    use core::{convert::TryFrom, fmt};
+    use serde::{
+        de::{self, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    /// Serializes the size as a string, see the [`Display`](core::fmt::Display) impl.
+    impl Serialize for SizeSpec {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_str(self)
+        }
+    }
+
+    /// Deserializes the size from a string, or an absolute size from a non-negative integer.
+    impl<'de> Deserialize<'de> for SizeSpec {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(SizeVisitor)
+        }
+    }
+
+    struct SizeVisitor;
+
+    impl<'de> Visitor<'de> for SizeVisitor {
+        type Value = SizeSpec;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a size like \"64MiB\" or \"-4K\", or a number of bytes")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<SizeSpec, E>
+        where
+            E: de::Error,
+        {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<SizeSpec, E>
+        where
+            E: de::Error,
+        {
+            Ok(SizeSpec::Absolute(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<SizeSpec, E>
+        where
+            E: de::Error,
+        {
+            u64::try_from(v)
+                .map(SizeSpec::Absolute)
+                .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorKind, ParseSizeError, SizeSpec};
+    use core::num::NonZeroU64;
+
+    fn nz(n: u64) -> NonZeroU64 {
+        NonZeroU64::new(n).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let cases = [
+            ("0", SizeSpec::Absolute(0)),
+            ("10", SizeSpec::Absolute(10)),
+            ("2K", SizeSpec::Absolute(2048)),
+            ("2k", SizeSpec::Absolute(2048)),
+            ("2KiB", SizeSpec::Absolute(2048)),
+            ("2KB", SizeSpec::Absolute(2000)),
+            ("64MiB", SizeSpec::Absolute(64 << 20)),
+            ("1G", SizeSpec::Absolute(1 << 30)),
+            ("3E", SizeSpec::Absolute(3 << 60)),
+            ("+1k", SizeSpec::Extend(1024)),
+            ("-4K", SizeSpec::Reduce(4096)),
+            ("<50", SizeSpec::AtMost(50)),
+            (">1MB", SizeSpec::AtLeast(1_000_000)),
+            ("/512", SizeSpec::RoundDown(nz(512))),
+            ("%4096", SizeSpec::RoundUp(nz(4096))),
+        ];
+
+        for (s, expected) in &cases {
+            assert_eq!(s.parse::<SizeSpec>().unwrap(), *expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in &[
+            "", "+", "K", "-K", "10X", "10KX", "10Kib", "10iB", " 10", "10 ", "+-1", "1.5K",
+        ] {
+            let e = s.parse::<SizeSpec>().unwrap_err();
+            assert_eq!(e, ParseSizeError(ErrorKind::Invalid), "{:?}", s);
+        }
+
+        for s in &["99999999999999999999", "16E", "1Z", "0Y", "1ZB"] {
+            let e = s.parse::<SizeSpec>().unwrap_err();
+            assert_eq!(e, ParseSizeError(ErrorKind::TooLarge), "{:?}", s);
+        }
+
+        let zero = ParseSizeError(ErrorKind::Zero);
+        assert_eq!("/0".parse::<SizeSpec>().unwrap_err(), zero);
+        assert_eq!("%0K".parse::<SizeSpec>().unwrap_err(), zero);
+    }
+
+    #[test]
+    fn apply() {
+        assert_eq!(SizeSpec::Absolute(10).apply(100), 10);
+        assert_eq!(SizeSpec::Extend(10).apply(100), 110);
+        assert_eq!(SizeSpec::Extend(10).apply(u64::MAX), u64::MAX);
+        assert_eq!(SizeSpec::Reduce(10).apply(100), 90);
+        assert_eq!(SizeSpec::Reduce(200).apply(100), 0);
+        assert_eq!(SizeSpec::AtMost(50).apply(100), 50);
+        assert_eq!(SizeSpec::AtMost(500).apply(100), 100);
+        assert_eq!(SizeSpec::AtLeast(50).apply(100), 100);
+        assert_eq!(SizeSpec::AtLeast(500).apply(100), 500);
+        assert_eq!(SizeSpec::RoundDown(nz(30)).apply(100), 90);
+        assert_eq!(SizeSpec::RoundUp(nz(30)).apply(100), 120);
+        assert_eq!(SizeSpec::RoundUp(nz(30)).apply(90), 90);
+        assert_eq!(SizeSpec::RoundUp(nz(1 << 20)).apply(0), 0);
+        assert_eq!(
+            SizeSpec::RoundUp(nz(1 << 20)).apply(u64::MAX),
+            u64::MAX >> 20 << 20
+        );
+
+        assert_eq!(SizeSpec::Extend(10).checked_apply(100), Some(110));
+        assert_eq!(
+            SizeSpec::Extend(10).checked_apply(u64::MAX - 10),
+            Some(u64::MAX)
+        );
+        assert_eq!(SizeSpec::Extend(11).checked_apply(u64::MAX - 10), None);
+        assert_eq!(SizeSpec::Reduce(200).checked_apply(100), Some(0));
+        assert_eq!(SizeSpec::RoundUp(nz(30)).checked_apply(100), Some(120));
+        assert_eq!(SizeSpec::RoundUp(nz(2)).checked_apply(u64::MAX), None);
+        assert_eq!(
+            SizeSpec::RoundUp(nz(3)).checked_apply(u64::MAX - 15),
+            Some(u64::MAX - 15)
+        );
+        assert_eq!(
+            SizeSpec::RoundDown(nz(2)).checked_apply(u64::MAX),
+            Some(u64::MAX - 1)
+        );
+
+        assert!(SizeSpec::Extend(0).is_relative());
+        assert!(!SizeSpec::Absolute(0).is_relative());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display() {
+        let cases = [
+            (SizeSpec::Absolute(0), "0"),
+            (SizeSpec::Absolute(1000), "1000"),
+            (SizeSpec::Extend(1024), "+1KiB"),
+            (SizeSpec::Reduce(1536), "-1536"),
+            (SizeSpec::AtMost(64 << 20), "<64MiB"),
+            (SizeSpec::AtLeast(3 << 60), ">3EiB"),
+            (SizeSpec::RoundDown(nz(1 << 63)), "/8EiB"),
+            (SizeSpec::RoundUp(nz(4096)), "%4KiB"),
+        ];
+
+        for (spec, expected) in &cases {
+            let s = spec.to_string();
+            assert_eq!(s, *expected);
+            assert_eq!(s.parse::<SizeSpec>().unwrap(), *spec);
+        }
+
+        let e = "16E".parse::<SizeSpec>().unwrap_err();
+        assert_eq!(e.to_string(), "size too large");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde::{
+            de::{
+                value::{Error, I64Deserializer, StrDeserializer, U64Deserializer},
+                IntoDeserializer,
+            },
+            Deserialize,
+        };
+
+        let de: StrDeserializer<'_, Error> = "%4K".into_deserializer();
+        assert_eq!(
+            SizeSpec::deserialize(de).unwrap(),
+            SizeSpec::RoundUp(nz(4096))
+        );
+        let de: StrDeserializer<'_, Error> = "4X".into_deserializer();
+        assert!(SizeSpec::deserialize(de).is_err());
+
+        let de: U64Deserializer<Error> = 10u64.into_deserializer();
+        assert_eq!(SizeSpec::deserialize(de).unwrap(), SizeSpec::Absolute(10));
+        let de: I64Deserializer<Error> = 10i64.into_deserializer();
+        assert_eq!(SizeSpec::deserialize(de).unwrap(), SizeSpec::Absolute(10));
+        let de: I64Deserializer<Error> = (-10i64).into_deserializer();
+        assert!(SizeSpec::deserialize(de).is_err());
+    }
+}
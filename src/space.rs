@@ -0,0 +1,189 @@
+//! Linux file space management with `fallocate(2)`.
+
+use crate::linux::fallocate_file;
+use std::{
+    fs::File,
+    io::{Error, ErrorKind},
+    ops::Range,
+};
+
+/// An extension trait for the space management operations of `fallocate(2)`.
+///
+/// These complement truncation by manipulating the allocated space and contents of ranges within a
+/// file. Support for the individual operations depends on the filesystem. If the filesystem
+/// rejects an operation, an error of kind [`Unsupported`](ErrorKind::Unsupported) is returned.
+///
+/// Empty ranges are accepted and do nothing. A range whose start lies after its end returns an
+/// error of kind [`InvalidInput`](ErrorKind::InvalidInput).
+///
+/// This trait is only available on Linux with the `fallocate` feature enabled.
+pub trait FileSpace {
+    /// Deallocates the given range, which will read back as zeros afterwards. The file size is not
+    /// changed.
+    ///
+    /// This uses `FALLOC_FL_PUNCH_HOLE`.
+    fn punch_hole(&self, range: Range<u64>) -> Result<(), Error>;
+
+    /// Zeroes the given range, preferably by converting it into unwritten extents rather than
+    /// writing out the zeros. The file is extended if the range ends after the end of the file.
+    ///
+    /// This uses `FALLOC_FL_ZERO_RANGE`.
+    fn zero_range(&self, range: Range<u64>) -> Result<(), Error>;
+
+    /// Removes the given range from the file, moving the data after it to its start. The file is
+    /// shortened by the length of the range.
+    ///
+    /// Most filesystems require the range to be aligned to the filesystem block size and to end
+    /// before the end of the file. This uses `FALLOC_FL_COLLAPSE_RANGE`.
+    fn collapse_range(&self, range: Range<u64>) -> Result<(), Error>;
+
+    /// Inserts a hole of the length of the given range at its start, moving the data after it back.
+    /// The file is extended by the length of the range.
+    ///
+    /// Most filesystems require the range to be aligned to the filesystem block size and to start
+    /// before the end of the file. This uses `FALLOC_FL_INSERT_RANGE`.
+    fn insert_range(&self, range: Range<u64>) -> Result<(), Error>;
+
+    /// Allocates space for the given range without changing the file size, so that later writes
+    /// to it don't fail due to a lack of space.
+    ///
+    /// This uses `FALLOC_FL_KEEP_SIZE`.
+    fn preallocate(&self, range: Range<u64>) -> Result<(), Error>;
+}
+
+fn fallocate_range(file: &File, mode: libc::c_int, range: Range<u64>) -> Result<(), Error> {
+    if range.start > range.end {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid range ({} > {})", range.start, range.end),
+        ));
+    }
+
+    if range.start == range.end {
+        return Ok(());
+    }
+
+    fallocate_file(file, mode, range.start, range.end - range.start)
+}
+
+impl FileSpace for File {
+    fn punch_hole(&self, range: Range<u64>) -> Result<(), Error> {
+        fallocate_range(
+            self,
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            range,
+        )
+    }
+
+    fn zero_range(&self, range: Range<u64>) -> Result<(), Error> {
+        fallocate_range(self, libc::FALLOC_FL_ZERO_RANGE, range)
+    }
+
+    fn collapse_range(&self, range: Range<u64>) -> Result<(), Error> {
+        fallocate_range(self, libc::FALLOC_FL_COLLAPSE_RANGE, range)
+    }
+
+    fn insert_range(&self, range: Range<u64>) -> Result<(), Error> {
+        fallocate_range(self, libc::FALLOC_FL_INSERT_RANGE, range)
+    }
+
+    fn preallocate(&self, range: Range<u64>) -> Result<(), Error> {
+        fallocate_range(self, libc::FALLOC_FL_KEEP_SIZE, range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileSpace;
+    use std::{
+        fs::File,
+        io::{ErrorKind, Read, Seek, SeekFrom, Write},
+        os::unix::fs::MetadataExt,
+        path::Path,
+    };
+
+    const BLOCK: usize = 4096;
+
+    fn file_in(dir: &Path) -> File {
+        let mut f = tempfile::tempfile_in(dir).unwrap();
+        let data: Vec<u8> = (0..BLOCK * 4).map(|i| (i / BLOCK) as u8 + 1).collect();
+        f.write_all(&data).unwrap();
+        f
+    }
+
+    fn contents(mut f: &File) -> Vec<u8> {
+        let mut buf = Vec::new();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    /// Returns the blocks of the file, identified by their first byte.
+    fn blocks(f: &File) -> Vec<u8> {
+        contents(f).chunks(BLOCK).map(|b| b[0]).collect()
+    }
+
+    fn check_common(f: &File) {
+        let b = BLOCK as u64;
+
+        f.punch_hole(b..2 * b).unwrap();
+        assert_eq!(blocks(f), &[1, 0, 3, 4]);
+
+        f.preallocate(4 * b..6 * b).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 4 * b);
+
+        f.punch_hole(b..b).unwrap();
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let e = f.punch_hole(2 * b..b).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    fn check_ext4(dir: &Path) {
+        let f = file_in(dir);
+        let b = BLOCK as u64;
+        assert_eq!(f.metadata().unwrap().blksize(), b);
+
+        check_common(&f);
+
+        f.zero_range(2 * b..3 * b).unwrap();
+        assert_eq!(blocks(&f), &[1, 0, 0, 4]);
+
+        f.collapse_range(0..b).unwrap();
+        assert_eq!(blocks(&f), &[0, 0, 4]);
+
+        f.insert_range(0..b).unwrap();
+        assert_eq!(blocks(&f), &[0, 0, 0, 4]);
+
+        // Misaligned
+        let e = f.collapse_range(0..1).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn tmpfs() {
+        let dir = Path::new("/dev/shm");
+        if !dir.is_dir() {
+            return;
+        }
+
+        let f = file_in(dir);
+        check_common(&f);
+
+        // Not supported by tmpfs
+        let e = f.collapse_range(0..BLOCK as u64).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Unsupported);
+        let e = f.insert_range(0..BLOCK as u64).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Unsupported);
+    }
+
+    /// Runs against an ext4 filesystem mounted at the directory given in the
+    /// `IO_TRUNCATE_EXT4_DIR` environment variable, e.g. a loop-mounted image file. Passes without
+    /// checking anything if it isn't set.
+    #[test]
+    fn ext4() {
+        if let Some(dir) = std::env::var_os("IO_TRUNCATE_EXT4_DIR") {
+            check_ext4(Path::new(&dir));
+        }
+    }
+}
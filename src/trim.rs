@@ -0,0 +1,320 @@
+//! Removal of trailing padding, like the unused zeros at the end of preallocated files.
+
+use crate::Truncate;
+use std::{
+    fs::File,
+    io::{Cursor, Error, Read, Seek, SeekFrom},
+};
+
+/// The size of the chunks that are read when scanning backwards.
+const CHUNK_LEN: u64 = 8 * 1024;
+
+/// A trait for removing trailing bytes matching a predicate.
+///
+/// Both methods scan backwards from the end of the object for the last byte that doesn't match,
+/// then truncate the object right after it with a single call to [`Truncate::truncate_u64`] and
+/// move the position to the new end. The new length is returned.
+pub trait TrimTrailing: Read + Seek + Truncate {
+    /// Remove all trailing bytes for which `pred` returns `true`.
+    ///
+    /// The default implementation reads the object backwards in chunks of 8 KiB.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::TrimTrailing;
+    /// # use std::io::Cursor;
+    /// let mut c = Cursor::new(b"data\n\n \n".to_vec());
+    /// assert_eq!(c.trim_trailing(|b| b.is_ascii_whitespace()).unwrap(), 4);
+    /// assert_eq!(c.get_ref(), b"data");
+    /// ```
+    fn trim_trailing<P>(&mut self, pred: P) -> Result<u64, Error>
+    where
+        P: FnMut(u8) -> bool,
+        Error: From<Self::Error>,
+    {
+        trim(self, pred)
+    }
+
+    /// Remove all trailing zero bytes.
+    ///
+    /// On Linux, the impls for files skip holes of sparse files with `SEEK_DATA` and `SEEK_HOLE`
+    /// instead of reading them, which makes trimming large preallocated files cheap. Other objects
+    /// are scanned like with [`trim_trailing`](TrimTrailing::trim_trailing).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::TrimTrailing;
+    /// # use std::io::Write;
+    /// let mut file = tempfile::tempfile().unwrap();
+    /// file.write_all(b"record\0\0").unwrap();
+    /// file.set_len(1 << 20).unwrap();
+    ///
+    /// assert_eq!(file.trim_trailing_zeros().unwrap(), 6);
+    /// ```
+    fn trim_trailing_zeros(&mut self) -> Result<u64, Error>
+    where
+        Error: From<Self::Error>,
+    {
+        self.trim_trailing(|b| b == 0)
+    }
+}
+
+/// Returns the end of the last byte in `start..end` that doesn't match `pred`.
+fn scan_back<R, P>(io: &mut R, start: u64, end: u64, pred: &mut P) -> Result<Option<u64>, Error>
+where
+    R: Read + Seek + ?Sized,
+    P: FnMut(u8) -> bool,
+{
+    let mut buf = [0; CHUNK_LEN as usize];
+    let mut pos = end;
+
+    while pos > start {
+        let chunk_start = pos.saturating_sub(CHUNK_LEN).max(start);
+        let chunk = &mut buf[..(pos - chunk_start) as usize];
+
+        io.seek(SeekFrom::Start(chunk_start))?;
+        io.read_exact(chunk)?;
+
+        if let Some(i) = chunk.iter().rposition(|&b| !pred(b)) {
+            return Ok(Some(chunk_start + i as u64 + 1));
+        }
+        pos = chunk_start;
+    }
+
+    Ok(None)
+}
+
+fn trim<T, P>(io: &mut T, mut pred: P) -> Result<u64, Error>
+where
+    T: Read + Seek + Truncate + ?Sized,
+    Error: From<T::Error>,
+    P: FnMut(u8) -> bool,
+{
+    let end = io.seek(SeekFrom::End(0))?;
+    let new_len = scan_back(io, 0, end, &mut pred)?.unwrap_or(0);
+    finish(io, new_len)
+}
+
+fn finish<T>(io: &mut T, new_len: u64) -> Result<u64, Error>
+where
+    T: Seek + Truncate + ?Sized,
+    Error: From<T::Error>,
+{
+    io.truncate_u64(new_len)?;
+    io.seek(SeekFrom::Start(new_len))?;
+    Ok(new_len)
+}
+
+/// Scans only the data regions of the file, starting with the last one.
+///
+/// `SEEK_DATA` only searches forwards, so the regions are found by searching windows before the
+/// end of the remaining part of the file, doubling the window size while it only contains holes.
+#[cfg(target_os = "linux")]
+fn trim_file_zeros(mut file: &File) -> Result<u64, Error> {
+    use crate::linux::{seek_data, seek_hole};
+
+    let mut end = file.metadata()?.len();
+    let mut window = CHUNK_LEN;
+
+    while end > 0 {
+        let start = end.saturating_sub(window);
+
+        // The last data region in `start..end`
+        let mut last = None;
+        let mut offset = start;
+        while offset < end {
+            let data = match seek_data(file, offset) {
+                Ok(Some(data)) if data < end => data,
+                Ok(_) => break,
+                // No support for `SEEK_DATA` at all, nothing has been changed yet
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                    return trim(&mut file, |b| b == 0)
+                }
+                Err(e) => return Err(e),
+            };
+            offset = seek_hole(file, data)?.min(end);
+            last = Some((data, offset));
+        }
+
+        match last {
+            Some((data, hole)) => {
+                if let Some(new_len) = scan_back(&mut file, data, hole, &mut |b| b == 0)? {
+                    return finish(&mut file, new_len);
+                }
+                end = data;
+            }
+            None => {
+                end = start;
+                window = window.saturating_mul(2);
+            }
+        }
+    }
+
+    finish(&mut file, 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn trim_file_zeros(mut file: &File) -> Result<u64, Error> {
+    trim(&mut file, |b| b == 0)
+}
+
+impl TrimTrailing for File {
+    fn trim_trailing_zeros(&mut self) -> Result<u64, Error> {
+        trim_file_zeros(self)
+    }
+}
+
+impl TrimTrailing for &File {
+    fn trim_trailing_zeros(&mut self) -> Result<u64, Error> {
+        trim_file_zeros(self)
+    }
+}
+
+impl<T> TrimTrailing for Cursor<T>
+where
+    T: AsRef<[u8]> + Truncate,
+    Error: From<T::Error>,
+{
+    /// Searches the contained buffer directly instead of reading it.
+    fn trim_trailing<P>(&mut self, mut pred: P) -> Result<u64, Error>
+    where
+        P: FnMut(u8) -> bool,
+    {
+        let new_len = self
+            .get_ref()
+            .as_ref()
+            .iter()
+            .rposition(|&b| !pred(b))
+            .map_or(0, |i| i as u64 + 1);
+        finish(self, new_len)
+    }
+}
+
+impl<T> TrimTrailing for &mut T
+where
+    T: TrimTrailing,
+    Error: From<T::Error>,
+{
+    fn trim_trailing<P>(&mut self, pred: P) -> Result<u64, Error>
+    where
+        P: FnMut(u8) -> bool,
+    {
+        (**self).trim_trailing(pred)
+    }
+
+    fn trim_trailing_zeros(&mut self) -> Result<u64, Error> {
+        (**self).trim_trailing_zeros()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrimTrailing, CHUNK_LEN};
+    use crate::{Len, Truncate};
+    use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+    /// Only has the default implementation of [`TrimTrailing`].
+    struct Reader(Cursor<Vec<u8>>);
+
+    impl Read for Reader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for Reader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    impl Truncate for Reader {
+        type Error = io::Error;
+
+        fn truncate(&mut self, new_len: usize) -> io::Result<()> {
+            Ok(self.0.truncate(new_len)?)
+        }
+    }
+
+    impl TrimTrailing for Reader {}
+
+    #[test]
+    fn cursor() {
+        let mut c = Cursor::new(b"a\0b\0\0".to_vec());
+        assert_eq!(c.trim_trailing_zeros().unwrap(), 3);
+        assert_eq!(c.get_ref(), b"a\0b");
+        assert_eq!(c.position(), 3);
+
+        assert_eq!(c.trim_trailing(|_| true).unwrap(), 0);
+        assert!(c.get_ref().is_empty());
+        assert_eq!(c.trim_trailing_zeros().unwrap(), 0);
+    }
+
+    #[test]
+    fn default() {
+        let mut data = vec![0; 3 * CHUNK_LEN as usize];
+        data[CHUNK_LEN as usize - 1] = b'x';
+        let mut r = Reader(Cursor::new(data));
+
+        assert_eq!(r.trim_trailing_zeros().unwrap(), CHUNK_LEN);
+        assert_eq!(r.0.get_ref().len() as u64, CHUNK_LEN);
+        assert_eq!(r.0.position(), CHUNK_LEN);
+
+        assert_eq!(r.trim_trailing(|_| true).unwrap(), 0);
+        assert!(r.0.get_ref().is_empty());
+    }
+
+    #[test]
+    fn file() {
+        let mut f = tempfile::tempfile().unwrap();
+
+        // Longer than a chunk, and a match exactly at the start of one
+        let mut data = vec![b' '; 3 * CHUNK_LEN as usize];
+        data[CHUNK_LEN as usize] = b'x';
+        f.write_all(&data).unwrap();
+
+        assert_eq!(f.trim_trailing(|b| b == b' ').unwrap(), CHUNK_LEN + 1);
+        assert_eq!(Len::len(&f).unwrap(), CHUNK_LEN + 1);
+        assert_eq!(f.stream_position().unwrap(), CHUNK_LEN + 1);
+
+        assert_eq!((&f).trim_trailing(|b| b != 0).unwrap(), 0);
+        assert_eq!(Len::len(&f).unwrap(), 0);
+    }
+
+    #[test]
+    fn file_zeros() {
+        let mut f = tempfile::tempfile().unwrap();
+        assert_eq!(f.trim_trailing_zeros().unwrap(), 0);
+
+        f.write_all(b"\0abc\0\0").unwrap();
+        f.set_len(3 * CHUNK_LEN).unwrap();
+        assert_eq!(f.trim_trailing_zeros().unwrap(), 4);
+        assert_eq!(Len::len(&f).unwrap(), 4);
+        assert_eq!(f.stream_position().unwrap(), 4);
+
+        // Only zeros, written out
+        f.write_all(&[0; 100]).unwrap();
+        assert_eq!((&f).trim_trailing_zeros().unwrap(), 4);
+
+        f.set_len(0).unwrap();
+        f.set_len(100).unwrap();
+        assert_eq!(f.trim_trailing_zeros().unwrap(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn file_sparse() {
+        let mut f = tempfile::tempfile().unwrap();
+
+        // Data, a hole, explicitly written zeros and a larger hole at the end
+        f.write_all(b"data").unwrap();
+        f.seek(SeekFrom::Start(1 << 20)).unwrap();
+        f.write_all(&[0; 4096]).unwrap();
+        f.set_len(8 << 20).unwrap();
+
+        assert_eq!(f.trim_trailing_zeros().unwrap(), 4);
+        assert_eq!(Len::len(&f).unwrap(), 4);
+    }
+}
@@ -0,0 +1,176 @@
+//! Truncation of text buffers that never splits a character.
+
+use crate::TruncateError;
+use std::{
+    cmp,
+    io::{Cursor, Error},
+};
+
+/// Where a text buffer may be cut when truncating it with [`TruncateUtf8`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Utf8Boundary {
+    /// Return an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput), wrapping a
+    /// [`TruncateError::NotCharBoundary`], if the length is not on a char boundary.
+    Error,
+    /// Round the length down to the previous char boundary.
+    Char,
+    /// Round the length down to the previous extended grapheme cluster boundary, so that for
+    /// example a letter and its combining accents are kept or removed together.
+    ///
+    /// Requires the `unicode-segmentation` feature.
+    #[cfg(feature = "unicode-segmentation")]
+    Grapheme,
+}
+
+/// A trait for text buffers that can be shortened without producing invalid UTF-8.
+pub trait TruncateUtf8 {
+    /// Truncate the text to at most the given new length in bytes, cutting it only at the given
+    /// kind of boundary.
+    ///
+    /// Returns the actual new length, which may be smaller than `new_len` if it was rounded down.
+    /// Lengths larger than the current length are an error, like for the [`Truncate`] impl of
+    /// `Vec<u8>`.
+    ///
+    /// [`Truncate`]: crate::Truncate
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use io_truncate::{TruncateUtf8, Utf8Boundary};
+    /// let mut s = String::from("añb");
+    /// assert!(s.truncate_utf8(2, Utf8Boundary::Error).is_err());
+    ///
+    /// assert_eq!(s.truncate_utf8(2, Utf8Boundary::Char).unwrap(), 1);
+    /// assert_eq!(s, "a");
+    /// ```
+    fn truncate_utf8(&mut self, new_len: usize, boundary: Utf8Boundary) -> Result<usize, Error>;
+}
+
+/// Finds the boundary at or before `new_len` in `s`.
+fn boundary_before(s: &str, new_len: usize, boundary: Utf8Boundary) -> Result<usize, Error> {
+    if new_len > s.len() {
+        return Err(TruncateError::GrowNotSupported {
+            requested: new_len as u64,
+            current: s.len() as u64,
+        }
+        .into());
+    }
+
+    match boundary {
+        Utf8Boundary::Error if s.is_char_boundary(new_len) => Ok(new_len),
+        Utf8Boundary::Error => Err(TruncateError::NotCharBoundary {
+            requested: new_len as u64,
+        }
+        .into()),
+        Utf8Boundary::Char => Ok((0..=new_len)
+            .rev()
+            .find(|i| s.is_char_boundary(*i))
+            .unwrap_or(0)),
+        #[cfg(feature = "unicode-segmentation")]
+        Utf8Boundary::Grapheme => {
+            use unicode_segmentation::UnicodeSegmentation;
+
+            if new_len == s.len() {
+                return Ok(new_len);
+            }
+
+            // Clusters have to be found in the whole string, the last cluster of the prefix may
+            // continue past `new_len`
+            Ok(s.grapheme_indices(true)
+                .map(|(i, _)| i)
+                .take_while(|i| *i <= new_len)
+                .last()
+                .unwrap_or(0))
+        }
+    }
+}
+
+impl TruncateUtf8 for String {
+    fn truncate_utf8(&mut self, new_len: usize, boundary: Utf8Boundary) -> Result<usize, Error> {
+        let new_len = boundary_before(self, new_len, boundary)?;
+        self.truncate(new_len);
+        Ok(new_len)
+    }
+}
+
+impl<T> TruncateUtf8 for Cursor<T>
+where
+    T: TruncateUtf8,
+{
+    /// Delegates to the contained [`TruncateUtf8`] impl. The cursor will be moved to the actual
+    /// new end of the text if it lies in the truncated area.
+    fn truncate_utf8(&mut self, new_len: usize, boundary: Utf8Boundary) -> Result<usize, Error> {
+        let new_len = self.get_mut().truncate_utf8(new_len, boundary)?;
+        self.set_position(cmp::min(new_len as u64, self.position()));
+        Ok(new_len)
+    }
+}
+
+impl<T> TruncateUtf8 for &mut T
+where
+    T: TruncateUtf8,
+{
+    fn truncate_utf8(&mut self, new_len: usize, boundary: Utf8Boundary) -> Result<usize, Error> {
+        (**self).truncate_utf8(new_len, boundary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TruncateUtf8, Utf8Boundary};
+    use crate::{Truncate, TruncateError};
+    use std::io::{Cursor, ErrorKind};
+
+    #[test]
+    fn string() {
+        let mut s = String::from("a€b");
+
+        let e = Truncate::truncate(&mut s, 2).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert!(matches!(e, TruncateError::NotCharBoundary { requested: 2 }));
+
+        let e = Truncate::truncate(&mut s, 6).unwrap_err();
+        assert!(matches!(e, TruncateError::GrowNotSupported { .. }));
+
+        Truncate::truncate(&mut s, 4).unwrap();
+        assert_eq!(s, "a€");
+    }
+
+    #[test]
+    fn char() {
+        let mut s = String::from("a€b");
+        assert_eq!(s.truncate_utf8(5, Utf8Boundary::Char).unwrap(), 5);
+        assert_eq!(s.truncate_utf8(3, Utf8Boundary::Char).unwrap(), 1);
+        assert_eq!(s, "a");
+        assert!(s.truncate_utf8(2, Utf8Boundary::Char).is_err());
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn grapheme() {
+        // "e" followed by a combining acute accent
+        let mut s = String::from("ae\u{301}b");
+        assert_eq!(s.truncate_utf8(3, Utf8Boundary::Char).unwrap(), 2);
+
+        let mut s = String::from("ae\u{301}b");
+        assert_eq!(s.truncate_utf8(4, Utf8Boundary::Grapheme).unwrap(), 4);
+        assert_eq!(s.truncate_utf8(3, Utf8Boundary::Grapheme).unwrap(), 1);
+        assert_eq!(s, "a");
+    }
+
+    #[test]
+    fn cursor() {
+        let mut c = Cursor::new(String::from("ab€"));
+        c.set_position(5);
+
+        Truncate::truncate(&mut c, 2).unwrap();
+        assert_eq!(c.position(), 2);
+
+        let mut c = Cursor::new(String::from("ab€"));
+        c.set_position(5);
+        assert_eq!(c.truncate_utf8(3, Utf8Boundary::Char).unwrap(), 2);
+        assert_eq!(c.get_ref(), "ab");
+        assert_eq!(c.position(), 2);
+    }
+}
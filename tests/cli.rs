@@ -0,0 +1,180 @@
+//! Tests for the `truncate` binary.
+
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+fn truncate(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_truncate"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn len(dir: &Path, name: &str) -> u64 {
+    fs::metadata(dir.join(name)).unwrap().len()
+}
+
+#[test]
+fn sizes() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+
+    let cases: &[(u64, &str, u64)] = &[
+        (0, "10", 10),
+        (0, "2K", 2048),
+        (0, "2KiB", 2048),
+        (0, "2KB", 2000),
+        (0, "1M", 1 << 20),
+        (0, "1G", 1 << 30),
+        (100, "+10", 110),
+        (100, "+1k", 1124),
+        (100, "-10", 90),
+        (100, "-512", 0),
+        (100, "<50", 50),
+        (100, "<500", 100),
+        (100, ">50", 100),
+        (100, ">500", 500),
+        (100, "/30", 90),
+        (100, "%30", 120),
+        (90, "%30", 90),
+    ];
+
+    for (initial, size, expected) in cases {
+        fs::write(dir.join("file"), vec![1; *initial as usize]).unwrap();
+
+        let output = truncate(dir, &["-s", size, "file"]);
+        assert!(output.status.success(), "{}: {:?}", size, output);
+        assert_eq!(len(dir, "file"), *expected, "{} from {}", size, initial);
+    }
+}
+
+#[test]
+fn option_forms() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+
+    for args in &[&["-s5", "a"][..], &["--size=5", "a"], &["--size", "5", "a"]] {
+        fs::remove_file(dir.join("a")).ok();
+        assert!(truncate(dir, args).status.success(), "{:?}", args);
+        assert_eq!(len(dir, "a"), 5);
+    }
+
+    // Files after `--` may start with a dash
+    assert!(truncate(dir, &["-s", "3", "--", "-b"]).status.success());
+    assert_eq!(len(dir, "-b"), 3);
+}
+
+#[test]
+fn multiple_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("a"), [0; 10]).unwrap();
+    fs::write(dir.join("b"), [0; 20]).unwrap();
+
+    assert!(truncate(dir, &["-s", "-5", "a", "b", "c"]).status.success());
+    assert_eq!(len(dir, "a"), 5);
+    assert_eq!(len(dir, "b"), 15);
+    assert_eq!(len(dir, "c"), 0);
+}
+
+#[test]
+fn no_create() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("a"), [0; 10]).unwrap();
+
+    assert!(truncate(dir, &["-c", "-s", "4", "a", "missing"])
+        .status
+        .success());
+    assert_eq!(len(dir, "a"), 4);
+    assert!(!dir.join("missing").exists());
+
+    assert!(truncate(dir, &["--no-create", "-s", "4", "missing"])
+        .status
+        .success());
+    assert!(!dir.join("missing").exists());
+}
+
+#[test]
+fn reference() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("ref"), [0; 100]).unwrap();
+    fs::write(dir.join("a"), [0; 10]).unwrap();
+
+    assert!(truncate(dir, &["-r", "ref", "a"]).status.success());
+    assert_eq!(len(dir, "a"), 100);
+
+    // Relative sizes are applied to the size of the reference
+    assert!(truncate(dir, &["--reference=ref", "-s", "+20", "a"])
+        .status
+        .success());
+    assert_eq!(len(dir, "a"), 120);
+
+    let output = truncate(dir, &["-r", "ref", "-s", "20", "a"]);
+    assert!(!output.status.success());
+    assert_eq!(len(dir, "a"), 120);
+
+    let output = truncate(dir, &["-r", "missing", "a"]);
+    assert!(!output.status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn io_blocks() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("a"), [0; 10]).unwrap();
+    let blksize = fs::metadata(dir.join("a")).unwrap().blksize();
+
+    assert!(truncate(dir, &["-o", "-s", "2", "a"]).status.success());
+    assert_eq!(len(dir, "a"), 2 * blksize);
+
+    assert!(truncate(dir, &["-co", "-s", "+1", "a"]).status.success());
+    assert_eq!(len(dir, "a"), 3 * blksize);
+}
+
+#[test]
+fn errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+
+    for args in &[
+        &["a"][..],
+        &["-s", "10"],
+        &["-s", "10X", "a"],
+        &["-s", "", "a"],
+        &["-s", "K", "a"],
+        &["-s", "/0", "a"],
+        &["-s", "99999999999999999999", "a"],
+        &["-s", "100E", "a"],
+        &["-x", "-s", "1", "a"],
+        &["--frobnicate", "-s", "1", "a"],
+        &["-s"],
+    ] {
+        let output = truncate(dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(!output.stderr.is_empty());
+    }
+
+    // Overflowing relative sizes are rejected before truncating
+    fs::write(dir.join("a"), b"x").unwrap();
+    let output = truncate(dir, &["-s", "+18446744073709551615", "a"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("size too large"));
+    assert_eq!(len(dir, "a"), 1);
+
+    // Failing files don't prevent the others from being truncated
+    fs::create_dir(dir.join("dir")).unwrap();
+    let output = truncate(dir, &["-s", "1", "dir", "a"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(len(dir, "a"), 1);
+}